@@ -0,0 +1,8792 @@
+//! mrdm tracks the TODO comments of a codebase. Besides the `mrdm` command
+//! line, the scan is usable as a library:
+//!
+//! ```no_run
+//! let config = mrdm::get_config(None)?;
+//! for (id, item) in mrdm::scan(&config)?.items {
+//!     println!("{id}: {}", item.title);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use config::Config;
+
+use log::{debug, info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{BufReader, BufWriter, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, LazyLock,
+    },
+    thread,
+};
+#[derive(Debug, Parser)] // requires `derive` feature
+#[command(name = "mrdm")]
+#[command(about = "A //TODO list utility for in-code project management", long_about = None)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Print the config in effect, after the config file, `MRDM_CONFIG`
+    /// and the flags overriding it, as JSON and exit
+    #[arg(long, global = true)]
+    print_config: bool,
+
+    /// When to color the output, `auto` honors `NO_COLOR` and only colors
+    /// a terminal
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Create missing parent directories of the `--out` file
+    #[arg(long, global = true)]
+    create_dirs: bool,
+
+    /// How failures are reported on stderr, `json` prints
+    /// `{"error": ..., "context": [...]}` for editor integrations
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+
+    /// Fail the scan when tracked TODOs lost their id in the source instead
+    /// of restoring them, see `todo check`
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Rewrite the sources even when it exceeds `max_changes` or
+    /// `max_new_ids` of the config
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// Also scan the paths and globs listed in this file, one per line,
+    /// overriding `include_from` of the config
+    #[arg(long, global = true)]
+    paths_from: Option<std::path::PathBuf>,
+
+    /// Run as if mrdm was started in this directory: the config, include
+    /// globs, data file, `--out` and links all resolve against it
+    #[arg(long, global = true)]
+    root: Option<std::path::PathBuf>,
+
+    /// Read the config from this file instead of `MRDM_CONFIG` or
+    /// `mrdm.json`
+    #[arg(long, global = true)]
+    config: Option<std::path::PathBuf>,
+
+    /// Apply the keys of this entry of `profiles` in the config over the
+    /// rest, e.g. `ci`. The `default` profile applies without it
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Match the categories in any case, overriding `ignore_case` of the
+    /// config
+    #[arg(long, global = true)]
+    ignore_case: bool,
+
+    /// Page `list`, `grep` and `diff` output through `$PAGER` even when
+    /// stdout is not a terminal. Without it, output is paged only on a
+    /// terminal
+    #[arg(long, global = true, conflicts_with = "no_pager")]
+    pager: bool,
+
+    /// Never page the output
+    #[arg(long, global = true)]
+    no_pager: bool,
+
+    /// Show the ids about to be injected into each file and ask before
+    /// rewriting it. Ignored without a terminal to ask on, and by
+    /// `list --dry-run`, which rewrites nothing
+    #[arg(long, global = true)]
+    confirm_each: bool,
+
+    /// Cut longer titles short with `…` in markdown output, overriding
+    /// `max_title_width` of the config
+    #[arg(long, global = true)]
+    max_title_width: Option<usize>,
+
+    /// Turn the order of `todo list --sort` around, whichever key it sorts by
+    #[arg(long, global = true)]
+    reverse: bool,
+
+    /// Scan and rewrite files on at most this many threads. `1` does it
+    /// all on one thread, in include order, for reproducible runs and
+    /// profiling
+    #[arg(long, alias = "threads", value_name = "N", global = true)]
+    jobs: Option<std::num::NonZeroUsize>,
+
+    /// The same as `--jobs 1`
+    #[arg(long, global = true, conflicts_with = "jobs")]
+    no_parallel: bool,
+}
+
+impl Cli {
+    /// Parses the command line like [`Parser::parse`], running a command
+    /// mrdm does not know as the alias of that name in the config.
+    pub fn parse_with_aliases() -> Self {
+        Self::try_parse_from_with_aliases(std::env::args_os()).unwrap_or_else(|err| err.exit())
+    }
+
+    /// [`Cli::parse_with_aliases`] of these arguments, with the error
+    /// instead of exiting.
+    pub fn try_parse_from_with_aliases<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString>,
+    {
+        let mut args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+        let mut expanded = HashSet::new();
+
+        loop {
+            let err = match Self::try_parse_from(&args) {
+                Ok(cli) => return Ok(cli),
+                Err(err) => err,
+            };
+            let Some((name, with_alias)) = expand_alias(&args, &err) else {
+                return Err(err);
+            };
+
+            if !expanded.insert(name.to_lowercase()) {
+                use clap::CommandFactory;
+                return Err(Self::command().error(
+                    clap::error::ErrorKind::InvalidSubcommand,
+                    format!("alias `{}` expands to itself", name),
+                ));
+            }
+            args = with_alias;
+        }
+    }
+}
+
+/// The command line with the unknown subcommand of `err` replaced by the
+/// words of its alias, or `None` when the config has no such alias.
+fn expand_alias(
+    args: &[std::ffi::OsString],
+    err: &clap::Error,
+) -> Option<(String, Vec<std::ffi::OsString>)> {
+    use clap::error::{ContextKind, ContextValue, ErrorKind};
+
+    if err.kind() != ErrorKind::InvalidSubcommand {
+        return None;
+    }
+    let Some(ContextValue::String(name)) = err.get(ContextKind::InvalidSubcommand) else {
+        return None;
+    };
+
+    // the occurrence clap took as the subcommand, not the value of a flag
+    // before it, which is where the global flags before it parse
+    let (position, globals) = (1..args.len())
+        .filter(|&position| args[position] == name.as_str())
+        .find_map(|position| {
+            Cli::try_parse_from(&args[..position])
+                .ok()
+                .map(|globals| (position, globals))
+        })?;
+
+    let cfg = get_config_at(
+        globals.root.as_deref(),
+        globals.config.as_deref(),
+        globals.profile.as_deref(),
+    )
+    .ok()?;
+    // the config loader lowercases map keys
+    let (_, alias) = cfg
+        .aliases
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))?;
+
+    let mut with_alias = args[..position].to_vec();
+    with_alias.extend(alias.split_whitespace().map(Into::into));
+    with_alias.extend_from_slice(&args[position + 1..]);
+    Some((name.clone(), with_alias))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl ErrorFormat {
+    /// Prints `err` and its causes to stderr.
+    fn report(self, err: &anyhow::Error) {
+        match self {
+            ErrorFormat::Human => eprintln!("Error: {:?}", err),
+            ErrorFormat::Json => eprintln!(
+                "{}",
+                serde_json::json!({
+                    "error": err.to_string(),
+                    "context": err.chain().skip(1).map(|cause| cause.to_string()).collect::<Vec<_>>(),
+                })
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// The single place deciding whether stdout gets ANSI colors.
+    fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                is_terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+const BOLD: &str = "1";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const DIM: &str = "2";
+
+/// Wraps `text` in an ANSI color sequence when `color` is on.
+fn paint(text: &str, code: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Manage TODOs in a file
+    Todo(Box<TodoArgs>),
+
+    // TODO(1): `mrdm commit` should help with committing with name and description
+    Init {
+        /// Write a config tuned for a stack instead of the generic default
+        #[arg(long, value_enum)]
+        preset: Option<Preset>,
+
+        /// List the available presets and exit
+        #[arg(long, conflicts_with = "preset")]
+        list_presets: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Preset {
+    /// Cargo layout, `//` comments
+    Rust,
+    /// Any `.py` file, `#` comments
+    Python,
+    /// JavaScript, TypeScript and styles under `src`, `//` and `/* */` comments
+    Web,
+}
+
+impl Preset {
+    fn config(self) -> &'static str {
+        match self {
+            Preset::Rust => include_str!("./config/presets/rust.json"),
+            Preset::Python => include_str!("./config/presets/python.json"),
+            Preset::Web => include_str!("./config/presets/web.json"),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct TodoArgs {
+    #[command(subcommand)]
+    command: TodoCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum TodoCommands {
+    /// List TODOs in a file
+    List {
+        // TODO(2): pattern should accept more tags like feat, fix, case-insensitive -> config file
+        /// The categories to search for, comma separated or repeated
+        /// example: "TODO,HACK" or -p TODO -p HACK
+        #[arg(short, value_delimiter = ',')]
+        pattern: Vec<String>,
+
+        /// The path to the file to search for TODOs
+        path: Option<std::path::PathBuf>,
+
+        /// Output file to write the TODOs to
+        /// If not provided, it will write to stdout
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+
+        /// Show at most this many TODOs
+        #[arg(long)]
+        max_results: Option<usize>,
+
+        /// Skip this many TODOs before showing any
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Markdown)]
+        format: OutputFormat,
+
+        /// Only list TODOs in files changed since the current branch forked
+        /// from `base_branch`
+        #[arg(long)]
+        new_only: bool,
+
+        /// Print the TODOs tracked in `.mrdm/data.json` without scanning
+        /// or rewriting any source file
+        #[arg(long, conflicts_with_all = ["pattern", "path", "new_only"])]
+        only_tracked: bool,
+
+        /// Scan the files of this `.tar` or `.tar.gz` instead of the working
+        /// tree, read-only, reporting in-archive paths
+        #[arg(long, conflicts_with_all = ["path", "only_tracked", "new_only", "stats"])]
+        archive: Option<std::path::PathBuf>,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Leave out the `(path:line)` location of each TODO
+        #[arg(long, alias = "no-location")]
+        flat: bool,
+
+        /// Report TODOs without an id as `new-N` instead of minting an id
+        /// and writing it to the source
+        #[arg(long, conflicts_with = "only_tracked")]
+        no_rewrite: bool,
+
+        /// Print how many files, lines and bytes were scanned, and how long
+        /// it took, to stderr
+        #[arg(long, conflicts_with = "only_tracked")]
+        stats: bool,
+
+        /// Only emit these fields, in this order, e.g. `id,title,path`.
+        /// Only applies to `--format json`, `markdown-table` and `csv`
+        #[arg(long, value_delimiter = ',')]
+        fields: Vec<String>,
+
+        /// Show TODOs with the same category and title once, in any case
+        /// when `ignore_case` is set
+        #[arg(long)]
+        dedupe: bool,
+
+        /// Only show TODOs that depend on a TODO which is still open
+        #[arg(long)]
+        blocked: bool,
+
+        /// Show the git author of each TODO's line, from `git blame`
+        #[arg(long, alias = "owner-from-blame", conflicts_with = "only_tracked")]
+        blame: bool,
+
+        /// Only show TODOs tracked or completed within this long, e.g. `7d`,
+        /// `24h` or `2w`. Items tracked before timestamps were never match
+        #[arg(long, value_parser = parse_duration)]
+        changed_since: Option<chrono::TimeDelta>,
+
+        /// Also show TODOs whose `@snooze(date)` has not passed yet
+        #[arg(long)]
+        show_snoozed: bool,
+
+        /// Only show the TODOs this run minted an id for, and sum them up on
+        /// stderr. Unlike --new-only, which looks at the files git changed
+        #[arg(long, conflicts_with_all = ["only_tracked", "archive", "no_rewrite"])]
+        only_new: bool,
+
+        /// Instead of the list, count the open TODOs per git author, from
+        /// `git blame`, most first. A JSON array with `--format json`,
+        /// a markdown table otherwise
+        #[arg(long, conflicts_with_all = ["only_tracked", "archive"])]
+        authors: bool,
+
+        /// Exit with this code when no TODO is left after the filters, for
+        /// a script to tell a clean tree apart
+        #[arg(long, value_name = "CODE", default_value_t = 0)]
+        exit_on_empty: u8,
+
+        /// Leave out done items, kept or read with --only-tracked, for a
+        /// view of the work remaining. The default when `hide_done` is set
+        #[arg(long, conflicts_with = "show_done")]
+        hide_done: bool,
+
+        /// Show done items even when `hide_done` is set
+        #[arg(long)]
+        show_done: bool,
+
+        /// Fail, listing where, when a TODO in the source has no id yet,
+        /// for a pre-commit hook. Implies --no-rewrite
+        #[arg(long, conflicts_with_all = ["only_tracked", "archive"])]
+        fail_if_untracked: bool,
+
+        /// Write `id\0path\0line\0category\0title\0` records, each ended
+        /// by one more NUL, for `xargs -0`. `--fields` picks other fields,
+        /// as for `--format csv`
+        #[arg(short = '0', long, conflicts_with_all = ["format", "authors"])]
+        null: bool,
+
+        /// Print nothing and exit with 3 when the scan finds the same TODOs
+        /// as `.mrdm` holds: none new, gone, reopened or completed. `list`
+        /// does not save what it finds, so a new TODO counts as a change on
+        /// every run until `todo done` tracks it
+        #[arg(long, conflicts_with_all = ["only_tracked", "archive"])]
+        output_on_change_only: bool,
+
+        /// Show when items were created, completed or are snoozed until as
+        /// `3 days ago` or `in 2 weeks`. json and csv keep ISO timestamps
+        #[arg(long, conflicts_with = "absolute_dates")]
+        relative_dates: bool,
+
+        /// Show those dates as timestamps in the configured `timezone`
+        #[arg(long)]
+        absolute_dates: bool,
+
+        /// List the TODOs with the ids this run would mint, without
+        /// writing them to the source
+        #[arg(
+            long,
+            conflicts_with_all = ["only_tracked", "archive", "no_rewrite", "fail_if_untracked"]
+        )]
+        dry_run: bool,
+
+        /// Instead of the list, print the unified diff of each file the ids
+        /// would be injected into
+        #[arg(long, requires = "dry_run")]
+        show_diff: bool,
+
+        /// The order of the list, see also --reverse
+        #[arg(long, value_enum, default_value_t = SortKey::Id)]
+        sort: SortKey,
+    },
+
+    Done {
+        #[arg(short, value_delimiter = ',')]
+        pattern: Vec<String>,
+
+        /// The path to the file to search for TODOs
+        path: Option<std::path::PathBuf>,
+
+        /// Output file to write the TODOs to
+        /// If not provided, it will write to stdout
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+
+        /// Sync without prompting: open items whose comment is gone are
+        /// marked as done, done items whose comment is back are reopened,
+        /// under their id and whatever their title is now. Each decision is
+        /// logged
+        #[arg(long, alias = "include-done-from-code")]
+        reconcile: bool,
+
+        /// Do not print the list, only the summary of what changed, on stdout
+        #[arg(long)]
+        summary_only: bool,
+
+        /// Only scan files staged in git, and stage the ids injected into
+        /// them, for a pre-commit hook
+        #[arg(long)]
+        staged: bool,
+
+        /// Mark these tracked ids as done without scanning, repeatable.
+        /// Their comments are renamed when `done_markers` maps the category
+        #[arg(
+            long = "id",
+            value_name = "ID",
+            conflicts_with_all = ["pattern", "path", "reconcile", "staged"]
+        )]
+        ids: Vec<String>,
+    },
+
+    /// Compare a previous data snapshot to the current scan
+    Diff {
+        /// A previously saved `.mrdm/data.json` to compare against
+        #[arg(long)]
+        against: std::path::PathBuf,
+
+        #[arg(short, value_delimiter = ',')]
+        pattern: Vec<String>,
+
+        /// The path to the file to search for TODOs
+        path: Option<std::path::PathBuf>,
+
+        /// Output file to write the diff to
+        /// If not provided, it will write to stdout
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+
+    /// Show a single tracked TODO with the code around it
+    Show {
+        /// The id of the TODO
+        id: String,
+
+        /// Lines of code to show before and after the TODO
+        #[arg(short = 'C', long, default_value_t = 3)]
+        context: usize,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ShowFormat::Text)]
+        format: ShowFormat,
+    },
+
+    /// Report tracked TODOs whose id was removed from the source
+    Check {
+        /// Filter by pattern
+        #[arg(short, long, value_delimiter = ',')]
+        pattern: Vec<String>,
+
+        /// Should be a glob pattern
+        path: Option<std::path::PathBuf>,
+
+        /// Write the original ids back into the source
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Print the regex TODOs are matched with and what it was built from,
+    /// without scanning
+    Regex {
+        /// Filter by pattern
+        #[arg(short, long, value_delimiter = ',')]
+        pattern: Vec<String>,
+    },
+
+    /// Remove every id from the TODO comments, to stop using mrdm
+    StripIds {
+        #[arg(short, value_delimiter = ',')]
+        pattern: Vec<String>,
+
+        /// The path to the file to strip ids from
+        path: Option<std::path::PathBuf>,
+
+        /// Rewrite the files, required since every TODO with an id changes
+        #[arg(long, required_unless_present = "dry_run")]
+        yes: bool,
+
+        /// Only print the lines that would change
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Also delete the tracked items in `.mrdm`
+        #[arg(long, conflicts_with = "dry_run")]
+        delete_data: bool,
+    },
+
+    /// Attach a note to a tracked TODO, shown by `todo show`. Notes live
+    /// in `.mrdm/data.json` only, the source comment stays as it is
+    Note {
+        /// The id of the TODO
+        id: String,
+
+        /// The note, e.g. "spoke with Alice, needs API v2"
+        text: String,
+    },
+
+    /// Print the transitions logged in `.mrdm/history.jsonl`, oldest
+    /// first, see `history` of the config
+    Log {
+        /// Only show the transitions of this id
+        id: Option<String>,
+    },
+
+    /// Render an SVG badge of the number of open tracked TODOs
+    Badge {
+        /// The SVG file to write, stdout when not given
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+
+    /// Search the titles of tracked TODOs, case-insensitively
+    Grep {
+        /// The text to look for
+        query: String,
+
+        /// Read QUERY as a regular expression
+        #[arg(long)]
+        regex: bool,
+
+        /// Search a fresh scan of the sources instead of the tracked items.
+        /// Nothing is rewritten, untracked TODOs show up as `new-N`
+        #[arg(long)]
+        scan: bool,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Markdown)]
+        format: OutputFormat,
+
+        /// Output file to write the matches to
+        /// If not provided, it will write to stdout
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct FilterArgs {
+    /// Only show TODOs of these categories, comma separated
+    #[arg(long, value_delimiter = ',')]
+    category: Vec<String>,
+
+    /// Only show TODOs with this status
+    #[arg(long, value_enum, default_value_t = StatusFilter::All)]
+    status: StatusFilter,
+
+    /// Only show TODOs carrying all of these `[label]`s, comma separated
+    #[arg(long, value_delimiter = ',')]
+    label: Vec<String>,
+
+    /// Only show TODOs whose path matches this glob, e.g. `src/net/**`
+    #[arg(long)]
+    path_filter: Option<glob::Pattern>,
+}
+
+/// The order of `todo list --sort`, ties staying in id order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum SortKey {
+    /// Numeric ids first, see [`id_sort_key`]
+    #[default]
+    Id,
+    /// By path, then line
+    File,
+    /// In the order of `patterns` in the config, the first one first
+    Priority,
+    /// By category name
+    Category,
+    /// Oldest first, items without a creation time last
+    Created,
+}
+
+impl SortKey {
+    /// Sorts `items`, already in id order, by this key. Items without a
+    /// creation time stay last with `reverse` too.
+    fn sort(self, items: &mut [(String, TodoItem)], patterns: &[String], reverse: bool) {
+        let priority = |item: &TodoItem| {
+            patterns
+                .iter()
+                .position(|pattern| pattern.eq_ignore_ascii_case(&item.category))
+                .unwrap_or(patterns.len())
+        };
+        let order = |(a_id, a): &(String, TodoItem), (b_id, b): &(String, TodoItem)| match self {
+            SortKey::Id => id_sort_key(a_id).cmp(&id_sort_key(b_id)),
+            SortKey::File => normalize_path(&a.path)
+                .cmp(normalize_path(&b.path))
+                .then(a.line.cmp(&b.line)),
+            SortKey::Priority => priority(a).cmp(&priority(b)),
+            SortKey::Category => a.category.cmp(&b.category),
+            SortKey::Created => match (a.created_at, b.created_at) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => std::cmp::Ordering::Equal,
+            },
+        };
+
+        items.sort_by(|a, b| {
+            let undated = |(_, item): &(String, TodoItem)| {
+                self == SortKey::Created && item.created_at.is_none()
+            };
+            let order = if reverse { order(b, a) } else { order(a, b) };
+            undated(a).cmp(&undated(b)).then(order)
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum StatusFilter {
+    Open,
+    Done,
+    All,
+}
+
+impl FilterArgs {
+    fn matches(&self, item: &TodoItem) -> bool {
+        let status = match self.status {
+            StatusFilter::Open => !item.done,
+            StatusFilter::Done => item.done,
+            StatusFilter::All => true,
+        };
+
+        status
+            && (self.category.is_empty() || self.category.contains(&item.category))
+            && self.label.iter().all(|label| item.labels.contains(label))
+            && self.path_filter.as_ref().is_none_or(|pattern| {
+                pattern.matches_path_with(normalize_path(&item.path), INCLUDE_MATCH_OPTIONS)
+            })
+    }
+}
+
+/// The formats of `todo show`, which prints one item and its code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ShowFormat {
+    /// The item, its notes and the code around it
+    #[value(alias = "markdown")]
+    Text,
+    /// The item as JSON, without the code
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputFormat {
+    /// A markdown task list
+    Markdown,
+    /// A JSON array of items, including the raw source line
+    Json,
+    /// Items grouped by directory and file, with a count per node
+    Tree,
+    /// A GitHub markdown table, one row per item
+    MarkdownTable,
+    /// Comma separated values with a header row, for spreadsheets
+    Csv,
+    /// A SARIF 2.1.0 log of the open items, for code scanning tools
+    Sarif,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CliConfig {
+    pub patterns: Vec<String>,
+    /// Globs of the files to scan. New ids are handed out in this order:
+    /// all files of the first entry, then those of the second that were
+    /// not matched yet, and so on, each by path then line.
+    pub include: Vec<String>,
+    pub out: Option<std::path::PathBuf>,
+
+    /// Tokens that end the tracked title, e.g. a second `//` or `;`.
+    /// Anything after the first terminator stays in the source untouched
+    /// but is not part of the title.
+    #[serde(default)]
+    pub title_terminators: Vec<String>,
+
+    /// Branch `--new-only` compares against, `main` or `master` when unset
+    #[serde(default)]
+    pub base_branch: Option<String>,
+
+    /// Only scan files with one of these extensions, e.g. `["rs", "py"]`.
+    /// Empty scans everything the include globs match.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+
+    #[serde(default)]
+    pub checkbox_style: CheckboxStyle,
+
+    /// Whether `list` and `diff` mint ids for TODOs that have none and
+    /// inject them into the source. `done` always does, since it has to
+    /// track every item.
+    #[serde(default = "default_true")]
+    pub auto_assign_ids: bool,
+
+    /// Tokens that start a comment, `["//"]` by default, e.g. `["#"]` for
+    /// Python. Injected ids keep the marker the TODO was written with.
+    #[serde(default = "default_comment_markers")]
+    pub comment_markers: Vec<String>,
+
+    /// A file listing more paths or globs to scan, one per line, for file
+    /// sets generated by another tool. Blank lines and `#` comments are
+    /// ignored.
+    #[serde(default)]
+    pub include_from: Option<std::path::PathBuf>,
+
+    /// How ids attach to the category: a bracket pair like `()` or `[]`
+    /// wraps the id, `TODO(3)`, anything else prefixes it, `" #"` gives
+    /// `TODO #3`. Both scanning and injection use it.
+    #[serde(default = "default_id_delimiters")]
+    pub id_delimiters: String,
+
+    /// Written before every id mrdm mints, e.g. `mrdm-` gives
+    /// `TODO(mrdm-3)`, to tell tool-managed ids from hand-written ones.
+    /// Ids are read with or without it. Empty by default.
+    #[serde(default)]
+    pub id_prefix: String,
+
+    /// The category a comment is renamed to when `todo done --id`
+    /// completes it, e.g. `{"TODO": "DONE"}`. Unmapped categories keep
+    /// their comment.
+    #[serde(default)]
+    pub done_markers: HashMap<String, String>,
+
+    /// Line anchor format of links in file output: `github` (the default)
+    /// and `gitlab` use `#L12`, `bitbucket` uses `#lines-12`, `custom` uses
+    /// `link_template`
+    #[serde(default)]
+    pub link_style: LinkStyle,
+
+    /// The anchor for `link_style: custom`, `{line}` is replaced by the
+    /// line number, e.g. `#line-{line}`
+    #[serde(default)]
+    pub link_template: Option<String>,
+
+    /// Render done items of markdown files struck through, in a folded
+    /// `<details>` section after the open ones. Stdout is unaffected.
+    #[serde(default)]
+    pub collapse_done: bool,
+
+    /// Where tracked items are stored: `json` (the default) rewrites
+    /// `.mrdm/data.json` as a whole, `sqlite` updates only the changed rows
+    /// of `.mrdm/data.db`. The latter needs mrdm built with the `sqlite`
+    /// feature and starts from `data.json` until it is first written.
+    #[serde(default)]
+    pub data_backend: DataBackend,
+
+    #[serde(default)]
+    pub badge: BadgeStyle,
+
+    /// Shell commands run after `todo done` saved the data file
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    /// Match the categories in any case, `// todo:` as well as `// TODO:`.
+    /// Categories are still shown as written.
+    #[serde(default)]
+    pub ignore_case: bool,
+
+    /// The only categories `todo check` accepts, e.g. `["TODO", "FIXME"]`.
+    /// Any `// WORD:` comment with another uppercase WORD fails it, even
+    /// when `patterns` does not match it. Empty allows everything.
+    #[serde(default)]
+    pub allowed_categories: Vec<String>,
+
+    /// Cut titles longer than this many characters short with `…` in
+    /// markdown lists and tables. JSON and CSV keep the full title.
+    /// Unlimited by default.
+    #[serde(default)]
+    pub max_title_width: Option<usize>,
+
+    /// How tracked TODOs are keyed: `id` (the default) injects a numeric id
+    /// into every comment, `hash` never touches the source and keys them
+    /// by a hash of their title and path instead. Editing either makes a
+    /// hashed TODO a new one.
+    #[serde(default)]
+    pub tracking: Tracking,
+
+    /// Refuse to rewrite more than this many files in one run, unless
+    /// `--force` is given, against an include glob that went too wide
+    #[serde(default)]
+    pub max_changes: Option<usize>,
+
+    /// Refuse to inject more than this many new ids in one run, unless
+    /// `--force` is given
+    #[serde(default)]
+    pub max_new_ids: Option<usize>,
+
+    /// Commands of their own, e.g. `report` for
+    /// `"todo list --format json --out reports/todos.json"`, run as
+    /// `mrdm report`. Words are split on whitespace, without quoting, and
+    /// arguments after the alias are appended. mrdm's own commands win
+    /// over an alias of the same name
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+
+    /// The lists that append to those of the `mrdm.json` files in parent
+    /// directories instead of replacing them, e.g. `["patterns"]`
+    #[serde(default)]
+    pub merge_lists: Vec<String>,
+
+    /// Leave done items out of `todo list` unless `--show-done` is given
+    #[serde(default)]
+    pub hide_done: bool,
+
+    /// Append every transition to `.mrdm/history.jsonl`: ids minted by a
+    /// scan, and what `todo done` marked done, reopened, removed or
+    /// recreated under a new id. Shown by `todo log`
+    #[serde(default)]
+    pub history: bool,
+
+    /// The encoding of the scanned files, a label like `latin1` or
+    /// `shift_jis`, UTF-8 by default. Lines ids are injected into are
+    /// written back in it, the others byte for byte
+    #[serde(default)]
+    pub encoding: Option<String>,
+
+    /// Named sets of keys that replace those above when selected with
+    /// `--profile`, e.g. `{"ci": {"include": ["src/**/*.rs"]}}`
+    #[serde(default)]
+    pub profiles: HashMap<String, serde_json::Value>,
+
+    /// The zone `todo show` displays timestamps in, an IANA name like
+    /// `Europe/Berlin` or `local`, the default. They are stored in UTC
+    #[serde(default)]
+    pub timezone: Option<String>,
+
+    /// The directory relative paths resolve against, from `--root`. Empty
+    /// for the current directory
+    #[serde(skip)]
+    pub root: std::path::PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataBackend {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+impl DataBackend {
+    fn validate(self) -> Result<()> {
+        if self == DataBackend::Sqlite && !cfg!(feature = "sqlite") {
+            return Err(Self::unsupported());
+        }
+
+        Ok(())
+    }
+
+    fn unsupported() -> anyhow::Error {
+        anyhow::anyhow!("data_backend `sqlite` needs mrdm built with `--features sqlite`")
+    }
+
+    /// The file the tracked items are stored in.
+    fn path(self) -> &'static str {
+        match self {
+            DataBackend::Json => OUT_PATH,
+            DataBackend::Sqlite => DB_PATH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tracking {
+    #[default]
+    Id,
+    Hash,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    #[default]
+    Github,
+    Gitlab,
+    Bitbucket,
+    Custom,
+}
+
+impl LinkStyle {
+    fn validate(self, template: Option<&str>) -> Result<()> {
+        match (self, template) {
+            (LinkStyle::Custom, Some(template)) if template.contains("{line}") => Ok(()),
+            (LinkStyle::Custom, _) => Err(anyhow::anyhow!(
+                "link_style `custom` needs a link_template containing `{{line}}`, e.g. `#line-{{line}}`"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The anchor pointing at `line` of a file.
+    fn fragment(self, template: Option<&str>, line: usize) -> String {
+        match (self, template) {
+            (LinkStyle::Bitbucket, _) => format!("#lines-{}", line),
+            (LinkStyle::Custom, Some(template)) => template.replace("{line}", &line.to_string()),
+            _ => format!("#L{}", line),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_comment_markers() -> Vec<String> {
+    vec!["//".to_string()]
+}
+
+fn default_id_delimiters() -> String {
+    "()".to_string()
+}
+
+/// The parsed `id_delimiters`, see [`CliConfig::id_delimiters`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdDelimiters {
+    open: String,
+    close: String,
+    /// the `id_prefix` of minted ids
+    prefix: String,
+}
+
+impl ::std::default::Default for IdDelimiters {
+    fn default() -> Self {
+        Self::parse(&default_id_delimiters()).unwrap()
+    }
+}
+
+impl IdDelimiters {
+    const PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+
+    fn parse(spec: &str) -> Result<Self> {
+        if spec.trim().is_empty() || spec.contains(|c: char| c.is_ascii_digit() || c == '\n') {
+            return Err(anyhow::anyhow!(
+                "invalid id_delimiters `{}`, expected a bracket pair like `()` or a prefix like ` #`",
+                spec
+            ));
+        }
+
+        let mut chars = spec.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(open), Some(close), None) if Self::PAIRS.contains(&(open, close)) => Ok(Self {
+                open: open.to_string(),
+                close: close.to_string(),
+                prefix: String::new(),
+            }),
+            _ => Ok(Self {
+                open: spec.to_string(),
+                close: String::new(),
+                prefix: String::new(),
+            }),
+        }
+    }
+
+    /// `3` as it is written after the category, e.g. `(3)`.
+    fn wrap(&self, id: &str) -> String {
+        format!("{}{}{}{}", self.open, self.prefix, id, self.close)
+    }
+
+    /// Whether `line` carries `category` with id `id`, prefixed or not.
+    fn mentioned_in(&self, line: &str, category: &str, id: &str) -> bool {
+        let plain = format!("{}{}{}{}", category, self.open, id, self.close);
+
+        line.contains(&plain) || line.contains(&format!("{}{}", category, self.wrap(id)))
+    }
+}
+
+impl CliConfig {
+    pub fn delimiters(&self) -> IdDelimiters {
+        IdDelimiters {
+            prefix: self.id_prefix.clone(),
+            ..IdDelimiters::parse(&self.id_delimiters).unwrap_or_default()
+        }
+    }
+
+    fn source_encoding(&self) -> &'static encoding_rs::Encoding {
+        parse_encoding(self.encoding.as_deref()).unwrap_or(encoding_rs::UTF_8)
+    }
+}
+
+/// The `encoding` of the config, UTF-8 when unset. Encodings that do not
+/// keep ASCII as it is, like UTF-16, would need every line re-encoded and
+/// are refused.
+fn parse_encoding(label: Option<&str>) -> Result<&'static encoding_rs::Encoding> {
+    let Some(label) = label else {
+        return Ok(encoding_rs::UTF_8);
+    };
+
+    match encoding_rs::Encoding::for_label(label.trim().as_bytes()) {
+        Some(encoding) if encoding.is_ascii_compatible() => Ok(encoding),
+        Some(encoding) => Err(anyhow::anyhow!(
+            "encoding `{}` is not supported, it does not keep ASCII as it is",
+            encoding.name()
+        )),
+        None => Err(anyhow::anyhow!(
+            "unknown encoding `{}`, expected a label like `utf-8`, `latin1` or `shift_jis`",
+            label
+        )),
+    }
+}
+
+/// How many bytes at the start of a file are looked at for a NUL, which
+/// only binary files contain.
+const BINARY_PROBE_BYTES: usize = 8000;
+
+/// The text of a source file, and the bytes it was decoded from unless it
+/// is UTF-8. `None` for a binary file.
+fn decode_source(
+    bytes: Vec<u8>,
+    encoding: &'static encoding_rs::Encoding,
+) -> Option<Result<(String, Option<Vec<u8>>)>> {
+    if bytes[..bytes.len().min(BINARY_PROBE_BYTES)].contains(&0) {
+        return None;
+    }
+
+    if encoding == encoding_rs::UTF_8 {
+        return Some(
+            String::from_utf8(bytes)
+                .map(|content| (content, None))
+                .context("the file is not UTF-8, see `encoding` of the config"),
+        );
+    }
+
+    let (content, _) = encoding.decode_without_bom_handling(&bytes);
+    Some(Ok((content.into_owned(), Some(bytes))))
+}
+
+/// The markdown task list syntax, `- [ ]` and `- [x]` by default.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CheckboxStyle {
+    /// The list bullet, one of `-`, `*` or `+`
+    bullet: String,
+    /// The mark of a done item, one of `x`, `X` or `✓`
+    done: String,
+}
+
+impl ::std::default::Default for CheckboxStyle {
+    fn default() -> Self {
+        Self {
+            bullet: "-".to_string(),
+            done: "x".to_string(),
+        }
+    }
+}
+
+impl CheckboxStyle {
+    const BULLETS: [&'static str; 3] = ["-", "*", "+"];
+    const DONE_MARKS: [&'static str; 3] = ["x", "X", "✓"];
+
+    fn validate(&self) -> Result<()> {
+        if !Self::BULLETS.contains(&self.bullet.as_str()) {
+            return Err(anyhow::anyhow!(
+                "invalid checkbox_style.bullet `{}`, expected one of {}",
+                self.bullet,
+                Self::BULLETS.join(" ")
+            ));
+        }
+
+        if !Self::DONE_MARKS.contains(&self.done.as_str()) {
+            return Err(anyhow::anyhow!(
+                "invalid checkbox_style.done `{}`, expected one of {}",
+                self.done,
+                Self::DONE_MARKS.join(" ")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl ::std::default::Default for CliConfig {
+    fn default() -> Self {
+        Self {
+            patterns: vec!["TODO".to_string()],
+            include: vec!["src/**/*".to_string()],
+            out: None,
+            title_terminators: vec![],
+            base_branch: None,
+            extensions: vec![],
+            checkbox_style: CheckboxStyle::default(),
+            auto_assign_ids: true,
+            comment_markers: default_comment_markers(),
+            include_from: None,
+            id_delimiters: default_id_delimiters(),
+            id_prefix: String::new(),
+            done_markers: HashMap::new(),
+            link_style: LinkStyle::default(),
+            link_template: None,
+            collapse_done: false,
+            data_backend: DataBackend::Json,
+            badge: BadgeStyle::default(),
+            hooks: Hooks::default(),
+            ignore_case: false,
+            allowed_categories: vec![],
+            max_title_width: None,
+            tracking: Tracking::Id,
+            max_changes: None,
+            max_new_ids: None,
+            aliases: HashMap::new(),
+            merge_lists: vec![],
+            hide_done: false,
+            timezone: None,
+            history: false,
+            encoding: None,
+            profiles: HashMap::new(),
+            root: std::path::PathBuf::new(),
+        }
+    }
+}
+
+/// Commands run through the shell with the data file as `$1` and in
+/// `MRDM_DATA_FILE`, e.g. to regenerate a docs page. Their output goes to
+/// stderr, a failing hook is only a warning.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Hooks {
+    /// After `todo done` scanned the sources and saved what it found
+    post_scan: Option<String>,
+    /// After `todo done` marked items as done, with or without a scan
+    post_done: Option<String>,
+}
+
+/// The environment variable hooks find the data file in.
+const HOOK_DATA_ENV: &str = "MRDM_DATA_FILE";
+
+/// Runs the given `(name, command)` hooks in order, reporting all that
+/// could not run or exited non-zero in one warning.
+fn run_hooks(hooks: &[(&str, &Option<String>)], backend: DataBackend, root: &std::path::Path) {
+    let data_path = backend.path();
+    let failed = hooks
+        .iter()
+        .filter_map(|(name, command)| {
+            let command = command.as_deref()?;
+            let mut shell = if cfg!(windows) {
+                let mut shell = std::process::Command::new("cmd");
+                shell.arg("/C").arg(command);
+                shell
+            } else {
+                let mut shell = std::process::Command::new("sh");
+                shell.arg("-c").arg(command).arg("mrdm");
+                shell
+            };
+
+            debug!("running hook {}: {}", name, command);
+            let status = shell
+                .arg(data_path)
+                .env(HOOK_DATA_ENV, data_path)
+                .current_dir(command_dir(root))
+                .stdout(std::io::stderr())
+                .status();
+            match status {
+                Ok(status) if status.success() => None,
+                Ok(status) => Some(format!("{} ({})", name, status)),
+                Err(e) => Some(format!("{} ({})", name, e)),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if !failed.is_empty() {
+        warn!("hooks failed: {}", failed.join(", "));
+    }
+}
+
+/// The `timezone` of the config, resolved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DisplayZone {
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+impl DisplayZone {
+    fn parse(timezone: Option<&str>) -> Result<Self> {
+        match timezone {
+            None => Ok(Self::Local),
+            Some(name) if name.eq_ignore_ascii_case("local") => Ok(Self::Local),
+            Some(name) => name.parse().map(Self::Named).map_err(|_| {
+                anyhow::anyhow!(
+                    "unknown timezone `{}`, expected an IANA name like `Europe/Berlin` or `local`",
+                    name
+                )
+            }),
+        }
+    }
+
+    /// `at` in this zone, to the minute, with its UTC offset.
+    fn format(&self, at: chrono::DateTime<chrono::Utc>) -> String {
+        const FORMAT: &str = "%Y-%m-%d %H:%M %:z";
+        match self {
+            Self::Local => at.with_timezone(&chrono::Local).format(FORMAT).to_string(),
+            Self::Named(tz) => at.with_timezone(tz).format(FORMAT).to_string(),
+        }
+    }
+}
+
+/// The look of `todo badge`, turning yellow at `yellow` open TODOs and red
+/// at `red`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BadgeStyle {
+    label: String,
+    yellow: usize,
+    red: usize,
+}
+
+impl ::std::default::Default for BadgeStyle {
+    fn default() -> Self {
+        Self {
+            label: "TODOs".to_string(),
+            yellow: 10,
+            red: 50,
+        }
+    }
+}
+
+impl BadgeStyle {
+    fn validate(&self) -> Result<()> {
+        if self.yellow > self.red {
+            return Err(anyhow::anyhow!(
+                "badge.yellow ({}) must not be above badge.red ({})",
+                self.yellow,
+                self.red
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn color(&self, count: usize) -> &'static str {
+        if count >= self.red {
+            "#e05d44"
+        } else if count >= self.yellow {
+            "#dfb317"
+        } else {
+            "#4c1"
+        }
+    }
+
+    /// A shields.io style SVG reading `label | count`. Text widths are
+    /// estimated, there is no font to measure them with.
+    fn render(&self, count: usize) -> String {
+        let label = self
+            .label
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;");
+        let count_text = count.to_string();
+
+        let text_width = |text: &str| text.chars().count() * 7 + 10;
+        let label_width = text_width(&self.label);
+        let count_width = text_width(&count_text);
+        let width = label_width + count_width;
+        let label_x = label_width as f64 / 2.0;
+        let count_x = label_width as f64 + count_width as f64 / 2.0;
+
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {count_text}">
+  <title>{label}: {count_text}</title>
+  <linearGradient id="s" x2="0" y2="100%">
+    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+  <clipPath id="r">
+    <rect width="{width}" height="20" rx="3" fill="#fff"/>
+  </clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{count_width}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x:.1}" y="15" fill="#010101" fill-opacity=".3">{label}</text>
+    <text x="{label_x:.1}" y="14">{label}</text>
+    <text x="{count_x:.1}" y="15" fill="#010101" fill-opacity=".3">{count_text}</text>
+    <text x="{count_x:.1}" y="14">{count_text}</text>
+  </g>
+</svg>
+"##,
+            color = self.color(count),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TodoItem {
+    pub title: String,
+    pub category: String,
+    #[serde(serialize_with = "serialize_path")]
+    pub path: std::path::PathBuf,
+    pub line: usize,
+
+    /// The 1-based column, in characters, the category starts at
+    #[serde(default)]
+    pub column: usize,
+
+    pub done: bool,
+
+    /// The full source line the TODO was found on
+    #[serde(default)]
+    pub raw: String,
+
+    /// Trailing `[label]`s of the title, e.g. `blocked` in `ship [blocked]`
+    #[serde(default)]
+    pub labels: Vec<String>,
+
+    /// Completed with `todo done --id` while the comment stays in the
+    /// source, so finding it again does not reopen it
+    #[serde(default)]
+    pub kept: bool,
+
+    /// Ids the title says it waits for, `after #3` or `depends: 3, 5`
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    /// The git author of the line, only filled in by `list --blame`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+
+    /// Free-form notes added with `todo note`, kept in the data file only
+    #[serde(default)]
+    pub notes: Vec<String>,
+
+    /// The 1-based notebook cell the TODO is in, `line` counting from the
+    /// start of the cell, see [`EMBEDDED_CODE`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cell: Option<usize>,
+
+    /// When `todo done` first tracked the item, unset for items tracked
+    /// before timestamps were
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// When `todo done` marked the item as done, unset while it is open
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// The date of a `@snooze(2025-09-01)` in the title, `list` hides the
+    /// item until then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<chrono::NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TodoList {
+    /// The layout of the data file, missing in files from before it was
+    /// versioned, see [`TodoList::migrate`]
+    #[serde(default)]
+    pub schema_version: u32,
+    pub items: std::collections::HashMap<String, TodoItem>,
+}
+
+/// The `schema_version` data files are written with.
+const SCHEMA_VERSION: u32 = 1;
+
+impl TodoList {
+    pub fn new(items: HashMap<String, TodoItem>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            items,
+        }
+    }
+
+    /// Upgrades a data file of an older `schema_version` in place. New
+    /// fields of an item are `#[serde(default)]`, this fills in what can be
+    /// derived from the fields it already had.
+    fn migrate(&mut self) -> Result<()> {
+        if self.schema_version > SCHEMA_VERSION {
+            return Err(anyhow::anyhow!(
+                "data file has schema_version {}, this mrdm only reads up to {}",
+                self.schema_version,
+                SCHEMA_VERSION
+            ));
+        }
+
+        if self.schema_version < 1 {
+            // titles still carry their `[label]`s and `after #3`s
+            for item in self.items.values_mut() {
+                if item.labels.is_empty() {
+                    let (title, labels) = split_labels(&item.title);
+                    (item.title, item.labels) = (title.to_string(), labels);
+                }
+                if item.depends_on.is_empty() {
+                    item.depends_on = parse_depends(&item.title, "");
+                }
+            }
+        }
+
+        self.schema_version = SCHEMA_VERSION;
+
+        Ok(())
+    }
+}
+
+const CONFIG_PATH: &str = "mrdm.json";
+const OUT_PATH: &str = ".mrdm/data.json";
+/// The tracked items of `data_backend: sqlite`
+const DB_PATH: &str = ".mrdm/data.db";
+const LOCK_PATH: &str = ".mrdm/lock";
+/// The transitions logged with `history: true`, one JSON object per line
+const HISTORY_PATH: &str = ".mrdm/history.jsonl";
+const PLACEHOLDER_ID_PREFIX: &str = "new";
+/// The exit code of `list --output-on-change-only` when nothing changed
+const UNCHANGED_EXIT_CODE: u8 = 3;
+
+/// The environment variable holding a whole config as JSON, for CI where
+/// mounting a `mrdm.json` is awkward.
+const CONFIG_ENV: &str = "MRDM_CONFIG";
+
+/// The profile applied when `--profile` is not given, if the config has one
+/// of this name.
+const DEFAULT_PROFILE: &str = "default";
+
+/// The config of `--config`, else the JSON in `MRDM_CONFIG`, else the
+/// `mrdm.json` files of the current directory and its parents, merged by
+/// [`read_config_layers`], else the defaults.
+pub fn get_config(config_path: Option<&std::path::Path>) -> Result<CliConfig> {
+    get_config_with_profile(config_path, None)
+}
+
+/// [`get_config`] with the keys of `profiles.<profile>` replacing those of
+/// the base config. Without a profile the `default` one applies, if any.
+pub fn get_config_with_profile(
+    config_path: Option<&std::path::Path>,
+    profile: Option<&str>,
+) -> Result<CliConfig> {
+    get_config_at(None, config_path, profile)
+}
+
+/// [`get_config_with_profile`] as if mrdm was started in `root`: the config
+/// is looked up from there, and [`CliConfig::root`] makes the relative paths
+/// of the scan, `.mrdm` and the output resolve against it too. The current
+/// directory of the process is left alone.
+pub fn get_config_at(
+    root: Option<&std::path::Path>,
+    config_path: Option<&std::path::Path>,
+    profile: Option<&str>,
+) -> Result<CliConfig> {
+    let root = root.map(std::path::Path::to_path_buf).unwrap_or_default();
+    if !root.as_os_str().is_empty() && !root.is_dir() {
+        return Err(anyhow::anyhow!(
+            "root `{}` is not a directory",
+            display_path(&root)
+        ));
+    }
+
+    let env_config = std::env::var(CONFIG_ENV).ok();
+    let mut cfg = find_config(&root, config_path, env_config.as_deref(), profile)?;
+    cfg.root = root;
+    Ok(cfg)
+}
+
+/// The config [`get_config_at`] reads, its `--config` relative to `root`,
+/// given the value of `MRDM_CONFIG`.
+fn find_config(
+    root: &std::path::Path,
+    config_path: Option<&std::path::Path>,
+    env_config: Option<&str>,
+    profile: Option<&str>,
+) -> Result<CliConfig> {
+    if let Some(config_path) = config_path {
+        return read_config_file(&root.join(config_path), profile);
+    }
+
+    if let Some(json) = env_config.filter(|json| !json.trim().is_empty()) {
+        return parse_config(
+            config::File::from_str(json, config::FileFormat::Json),
+            profile,
+        )
+        .with_context(|| format!("could not parse config from `{}`", CONFIG_ENV));
+    }
+
+    if let Ok(current_dir) = std::env::current_dir() {
+        let layers = config_layers(&current_dir.join(root));
+
+        if !layers.is_empty() {
+            return read_config_layers(&layers, profile);
+        }
+    }
+
+    if let Some(profile) = profile {
+        return Err(anyhow::anyhow!(
+            "unknown profile `{}`, there is no config file",
+            profile
+        ));
+    }
+
+    Ok(CliConfig::default())
+}
+
+/// Builds the config with the selected profile, see
+/// [`get_config_with_profile`].
+fn build_config(
+    mut builder: config::builder::ConfigBuilder<config::builder::DefaultState>,
+    profile: Option<&str>,
+) -> Result<CliConfig> {
+    // the config loader lowercases map keys
+    let name = profile.unwrap_or(DEFAULT_PROFILE).to_lowercase();
+    match builder
+        .build_cloned()?
+        .get_table(&format!("profiles.{}", name))
+    {
+        Ok(keys) => {
+            for (key, value) in keys {
+                builder = builder.set_override(key, value)?;
+            }
+        }
+        Err(_) if profile.is_none() => {}
+        Err(_) => return Err(anyhow::anyhow!("unknown profile `{}`", name)),
+    }
+
+    Ok(builder.build()?.try_deserialize()?)
+}
+
+/// The `mrdm.json` files from `dir` up to the root of its git repository,
+/// or of the filesystem outside one, outermost first.
+fn config_layers(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut layers = vec![];
+    for dir in dir.ancestors() {
+        let config_path = dir.join(CONFIG_PATH);
+        if config_path.is_file() {
+            layers.push(config_path);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    layers.reverse();
+    layers
+}
+
+/// Merges config files, each inner one overriding the keys it sets: a
+/// scalar or list replaces the outer value, a map is merged key by key.
+/// The lists named in `merge_lists` instead append, outermost first.
+/// Relative paths resolve against [`CliConfig::root`] either way.
+fn read_config_layers(layers: &[std::path::PathBuf], profile: Option<&str>) -> Result<CliConfig> {
+    let read = || -> Result<CliConfig> {
+        let file = |path: &std::path::PathBuf| {
+            config::File::from(path.as_path()).format(config::FileFormat::Json)
+        };
+
+        let mut builder = layers.iter().fold(Config::builder(), |builder, path| {
+            builder.add_source(file(path))
+        });
+
+        let merge_lists = builder
+            .build_cloned()?
+            .get::<Vec<String>>("merge_lists")
+            .unwrap_or_default();
+        for key in merge_lists {
+            let mut entries = vec![];
+            for path in layers {
+                let layer = Config::builder().add_source(file(path)).build()?;
+                entries.extend(layer.get_array(&key).unwrap_or_default());
+            }
+            builder = builder.set_override(&key, entries)?;
+        }
+
+        build_config(builder, profile)
+    };
+
+    read().with_context(|| {
+        let paths = layers
+            .iter()
+            .map(|path| format!("`{}`", display_path(path)))
+            .collect::<Vec<_>>();
+        format!("could not read config file {}", paths.join(" merged with "))
+    })
+}
+
+fn read_config_file(config_path: &std::path::Path, profile: Option<&str>) -> Result<CliConfig> {
+    let file = config::File::from(config_path).format(config::FileFormat::Json);
+
+    parse_config(file, profile)
+        .with_context(|| format!("could not read config file `{}`", display_path(config_path)))
+}
+
+fn parse_config<S>(source: S, profile: Option<&str>) -> Result<CliConfig>
+where
+    S: config::Source + Send + Sync + 'static,
+{
+    build_config(Config::builder().add_source(source), profile)
+}
+
+/// Advisory lock held while a command reads and rewrites `.mrdm` and the
+/// scanned sources. The lock file stores the owner's pid and is removed when
+/// the guard is dropped.
+struct DataLock {
+    path: std::path::PathBuf,
+}
+
+impl DataLock {
+    fn acquire(root: &std::path::Path) -> Result<Self> {
+        let path = root.join(LOCK_PATH);
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("could not create directory `{}`", dir.display()))?;
+        }
+
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    write!(file, "{}", std::process::id())
+                        .with_context(|| format!("could not write file `{}`", path.display()))?;
+
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let holder = std::fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|pid| pid.trim().parse::<u32>().ok());
+
+                    match holder {
+                        Some(pid) if !process_alive(pid) => {
+                            debug!("removing stale lock held by pid {}", pid);
+                            std::fs::remove_file(&path).with_context(|| {
+                                format!("could not remove stale lock `{}`", path.display())
+                            })?;
+                        }
+                        Some(pid) => {
+                            return Err(anyhow::anyhow!(
+                                "another mrdm process (pid {}) is running, lock `{}` is held",
+                                pid,
+                                path.display()
+                            ))
+                        }
+                        None => {
+                            return Err(anyhow::anyhow!(
+                                "lock `{}` is held, remove it if no other mrdm process is running",
+                                path.display()
+                            ))
+                        }
+                    }
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("could not create lock `{}`", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Best effort liveness check, only Linux can tell a stale lock apart.
+fn process_alive(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        std::path::Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+/// `root` as the directory to run a command in, `.` when it is empty.
+fn command_dir(root: &std::path::Path) -> &std::path::Path {
+    if root.as_os_str().is_empty() {
+        std::path::Path::new(".")
+    } else {
+        root
+    }
+}
+
+/// Runs git in `root` with `args`, returning its stdout if it succeeded.
+fn git_output(root: &std::path::Path, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(command_dir(root))
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string(),
+    )
+}
+
+/// Fills in the `author` of every item with one `git blame` per file.
+/// Items outside a git repository or in untracked files keep none.
+fn blame_todo_items(todo_items: &mut [(String, TodoItem)], root: &std::path::Path) {
+    if git_output(root, &["rev-parse", "--is-inside-work-tree"]).is_none() {
+        warn!("--blame needs a git repository, showing no authors");
+        return;
+    }
+
+    let mut by_path = HashMap::<std::path::PathBuf, Vec<&mut TodoItem>>::new();
+    for (_, item) in todo_items.iter_mut() {
+        by_path.entry(item.path.clone()).or_default().push(item);
+    }
+
+    for (path, items) in by_path {
+        let ranges = items
+            .iter()
+            .map(|item| format!("{},{}", item.line, item.line))
+            .collect::<Vec<_>>();
+
+        let mut args = vec!["blame", "--line-porcelain"];
+        for range in &ranges {
+            args.extend(["-L", range]);
+        }
+        let path_arg = display_path(&path);
+        args.extend(["--", &path_arg]);
+
+        let Some(porcelain) = git_output(root, &args) else {
+            debug!("could not blame `{}`, it may be untracked", path_arg);
+            continue;
+        };
+
+        let authors = parse_blame_authors(&porcelain);
+        for item in items {
+            item.author = authors.get(&item.line).cloned();
+        }
+    }
+}
+
+/// The number of items per author, most first, then by name. Items
+/// `git blame` could not attribute count as `unknown`.
+fn count_authors(todo_items: &[(String, TodoItem)]) -> Vec<(String, usize)> {
+    let mut counts = HashMap::<&str, usize>::new();
+    for (_, item) in todo_items {
+        *counts
+            .entry(item.author.as_deref().unwrap_or("unknown"))
+            .or_default() += 1;
+    }
+
+    let mut counts = counts
+        .into_iter()
+        .map(|(author, count)| (author.to_string(), count))
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// Renders `list --authors`, as JSON for `--format json` and as a
+/// markdown table for any other format.
+fn write_author_counts(
+    counts: &[(String, usize)],
+    format: OutputFormat,
+    outbuf: &mut impl Write,
+) -> Result<()> {
+    if matches!(format, OutputFormat::Json) {
+        let rows = counts
+            .iter()
+            .map(|(author, count)| serde_json::json!({ "author": author, "count": count }))
+            .collect::<Vec<_>>();
+        serde_json::to_writer_pretty(&mut *outbuf, &rows).context("could not write the JSON")?;
+        writeln!(outbuf)?;
+        return Ok(());
+    }
+
+    writeln!(outbuf, "| Author | Open |")?;
+    writeln!(outbuf, "| --- | --- |")?;
+    for (author, count) in counts {
+        writeln!(outbuf, "| {} | {} |", markdown_title(author), count)?;
+    }
+
+    Ok(())
+}
+
+/// The author of each final line in `git blame --line-porcelain` output.
+fn parse_blame_authors(porcelain: &str) -> HashMap<usize, String> {
+    let mut authors = HashMap::new();
+    let mut line = None;
+
+    for row in porcelain.lines() {
+        if let Some(author) = row.strip_prefix("author ") {
+            if let Some(line) = line.take() {
+                authors.insert(line, author.to_string());
+            }
+            continue;
+        }
+
+        // every entry starts with `<sha> <original line> <final line> ...`,
+        // the sha being SHA-1 or, in a SHA-256 repository, 64 digits long
+        let mut header = row.split(' ');
+        if let (Some(sha), Some(_), Some(final_line)) =
+            (header.next(), header.next(), header.next())
+        {
+            if matches!(sha.len(), 40 | 64) && sha.bytes().all(|b| b.is_ascii_hexdigit()) {
+                line = final_line.parse().ok();
+            }
+        }
+    }
+
+    authors
+}
+
+fn resolve_base_branch(cfg: &CliConfig) -> Option<String> {
+    if let Some(branch) = &cfg.base_branch {
+        return Some(branch.clone());
+    }
+
+    ["main", "master"]
+        .into_iter()
+        .find(|branch| {
+            git_output(&cfg.root, &["rev-parse", "--verify", "--quiet", branch]).is_some()
+        })
+        .map(str::to_string)
+}
+
+/// Files changed or added since `HEAD` forked from the base branch,
+/// relative to [`CliConfig::root`]. `None` outside a git repository.
+fn changed_since_base(cfg: &CliConfig) -> Option<Vec<std::path::PathBuf>> {
+    let base = resolve_base_branch(cfg)?;
+    let merge_base = git_output(&cfg.root, &["merge-base", "HEAD", &base])?;
+
+    let changed = git_output(
+        &cfg.root,
+        &["diff", "--name-only", "--relative", &merge_base],
+    )?;
+    let untracked = git_output(&cfg.root, &["ls-files", "--others", "--exclude-standard"])?;
+
+    Some(
+        changed
+            .lines()
+            .chain(untracked.lines())
+            .filter(|line| !line.is_empty())
+            .map(std::path::PathBuf::from)
+            .collect(),
+    )
+}
+
+/// Files added, copied, modified or renamed in the index, relative to
+/// `root`. `None` outside of a git repository.
+fn staged_files(root: &std::path::Path) -> Option<Vec<std::path::PathBuf>> {
+    let staged = git_output(
+        root,
+        &[
+            "diff",
+            "--cached",
+            "--name-only",
+            "--relative",
+            "--diff-filter=ACMR",
+        ],
+    )?;
+
+    Some(
+        staged
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(std::path::PathBuf::from)
+            .collect(),
+    )
+}
+
+/// Renders `path` with `/` separators on every platform, so links and JSON
+/// stay portable. Filesystem access keeps using the real path.
+fn display_path(path: &std::path::Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn serialize_path<S: serde::Serializer>(
+    path: &std::path::Path,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&display_path(path))
+}
+
+/// Drops a leading `./` so glob results compare equal to git's paths.
+/// How a glob matches a path here, as it does walking the tree for the
+/// include globs: `*` stays within a directory.
+const INCLUDE_MATCH_OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+fn normalize_path(path: &std::path::Path) -> &std::path::Path {
+    path.strip_prefix(".").unwrap_or(path)
+}
+
+fn has_allowed_extension(path: &std::path::Path, extensions: &[String]) -> bool {
+    extensions.is_empty()
+        || path
+            .extension()
+            .is_some_and(|ext| extensions.iter().any(|allowed| ext == allowed.as_str()))
+}
+
+/// Cuts the captured title at the earliest configured terminator.
+fn trim_title<'a>(title: &'a str, terminators: &[String]) -> &'a str {
+    let end = terminators
+        .iter()
+        .filter(|t| !t.is_empty())
+        .filter_map(|t| title.find(t.as_str()))
+        .min()
+        .unwrap_or(title.len());
+
+    title[..end].trim_end()
+}
+
+/// Splits trailing `[a][b]` tags off a title. A tag has to follow a space
+/// or another tag, so `index[0]` stays part of the title.
+fn split_labels(title: &str) -> (&str, Vec<String>) {
+    let mut rest = title.trim_end();
+    let mut labels = vec![];
+
+    while let Some(open) = rest.strip_suffix(']').and_then(|r| r.rfind('[')) {
+        let label = &rest[open + 1..rest.len() - 1];
+        let before = &rest[..open];
+
+        if label.is_empty()
+            || label.contains(']')
+            || !(before.is_empty() || before.ends_with([' ', ']']))
+        {
+            break;
+        }
+
+        labels.push(label.to_string());
+        rest = before;
+    }
+
+    labels.reverse();
+
+    if labels.is_empty() {
+        (title, labels)
+    } else {
+        (rest.trim_end(), labels)
+    }
+}
+
+/// Takes a `@snooze(2025-09-01)` out of a title. A token whose date does
+/// not parse stays in the title.
+fn split_snooze(title: &str) -> (std::borrow::Cow<'_, str>, Option<chrono::NaiveDate>) {
+    static SNOOZE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\s*@snooze\((\d{4}-\d{2}-\d{2})\)").unwrap());
+
+    let Some((token, date)) = SNOOZE.captures_iter(title).find_map(|caps| {
+        let date = chrono::NaiveDate::parse_from_str(&caps[1], "%Y-%m-%d").ok()?;
+        Some((caps.get(0).unwrap(), date))
+    }) else {
+        return (title.into(), None);
+    };
+
+    let title = format!("{}{}", &title[..token.start()], &title[token.end()..]);
+    (title.trim().to_string().into(), Some(date))
+}
+
+/// Whether `item` is snoozed past `today`. A snooze that ran out is over.
+fn is_snoozed(item: &TodoItem, today: chrono::NaiveDate) -> bool {
+    item.snoozed_until.is_some_and(|until| until > today)
+}
+
+/// The ids a title depends on, `after #3` or `depends: 3, 5`, with the
+/// `id_prefix` of minted ids dropped.
+fn parse_depends(title: &str, prefix: &str) -> Vec<String> {
+    static DEPENDS: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)\bafter #([\w-]+)|\bdepends:\s*([\w-]+(?:\s*,\s*[\w-]+)*)").unwrap()
+    });
+
+    let mut depends = vec![];
+    for caps in DEPENDS.captures_iter(title) {
+        let ids = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+
+        for id in ids.split(',').map(str::trim) {
+            let id = id.strip_prefix(prefix).unwrap_or(id).to_string();
+            if !depends.contains(&id) {
+                depends.push(id);
+            }
+        }
+    }
+
+    depends
+}
+
+/// Whether `item` waits for a TODO that is still open.
+fn is_blocked(item: &TodoItem, open: &HashSet<String>) -> bool {
+    item.depends_on.iter().any(|id| open.contains(id))
+}
+
+/// A TODO comment found while scanning. `id` stays `None` until one is
+/// minted for it.
+#[derive(Debug, Clone)]
+struct TodoMatch {
+    id: Option<String>,
+    /// the id was minted by this run and still has to be written to the file
+    minted: bool,
+    item: TodoItem,
+}
+
+#[derive(Debug)]
+struct ScannedFile {
+    path: std::path::PathBuf,
+    /// index of the first include entry that matched the file
+    root: usize,
+    content: String,
+    /// the matches were found in code extracted from `content`, which can
+    /// not be rewritten, see [`EMBEDDED_CODE`]
+    scan_only: bool,
+    /// the bytes `content` was decoded from, when not UTF-8, see
+    /// [`decode_source`]
+    encoded: Option<(&'static encoding_rs::Encoding, Vec<u8>)>,
+    matches: Vec<TodoMatch>,
+}
+
+impl ScannedFile {
+    /// Include order, then path: the order ids are assigned in.
+    fn order_key(&self) -> (usize, &std::path::Path) {
+        (self.root, &self.path)
+    }
+}
+
+/// Scan phase: collects the TODOs of one file without modifying it.
+fn scan_file(
+    path: &std::path::Path,
+    re: &Regex,
+    cfg: &CliConfig,
+    stats: &ScanStats,
+    jobs: Option<usize>,
+) -> Result<ScannedFile> {
+    let read_error = || format!("could not read file `{}`", &path.display());
+    let bytes = std::fs::read(cfg.root.join(path)).with_context(read_error)?;
+    let encoding = cfg.source_encoding();
+    let Some(decoded) = decode_source(bytes, encoding) else {
+        debug!("skipping binary file: {}", path.display());
+        return Ok(ScannedFile {
+            path: path.to_path_buf(),
+            root: 0,
+            content: String::new(),
+            scan_only: true,
+            encoded: None,
+            matches: vec![],
+        });
+    };
+    let (content, original) = decoded.with_context(read_error)?;
+
+    let lines = content.lines().collect::<Vec<_>>();
+
+    stats.files.fetch_add(1, Ordering::Relaxed);
+    stats.lines.fetch_add(lines.len(), Ordering::Relaxed);
+    stats.bytes.fetch_add(content.len(), Ordering::Relaxed);
+
+    let (matches, scan_only) = match scan_embedded(path, &content, re, cfg) {
+        Some(matches) => (matches, true),
+        None if content.len() < PARALLEL_SCAN_BYTES => {
+            (scan_lines(&lines, 0, path, re, cfg), false)
+        }
+        None if jobs == Some(1) => (scan_lines(&lines, 0, path, re, cfg), false),
+        None => {
+            let threads =
+                jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+            (scan_lines_parallel(&lines, threads, path, re, cfg), false)
+        }
+    };
+
+    Ok(ScannedFile {
+        path: path.to_path_buf(),
+        root: 0,
+        content,
+        scan_only,
+        encoded: original.map(|bytes| (encoding, bytes)),
+        matches,
+    })
+}
+
+/// Code embedded in a file that is not plain source, with where each of
+/// its lines came from.
+struct EmbeddedCode {
+    code: String,
+    /// the 1-based cell and line within the cell of each line of `code`
+    origins: Vec<(usize, usize)>,
+}
+
+/// Reads the [`EmbeddedCode`] of a file's content.
+type ExtractCode = fn(&str) -> Result<EmbeddedCode>;
+
+/// Extracts the code of files that can not be scanned line by line, by
+/// extension. Their TODOs are located by cell, and since the extracted code
+/// can not be written back, they are scan-only: new ones get placeholder
+/// ids, as with `--no-rewrite`.
+const EMBEDDED_CODE: &[(&str, ExtractCode)] = &[("ipynb", notebook_code)];
+
+/// The code cells of a Jupyter notebook, whose `source` is a string or a
+/// list of lines.
+fn notebook_code(content: &str) -> Result<EmbeddedCode> {
+    let notebook: serde_json::Value = serde_json::from_str(content)?;
+    let cells = notebook["cells"]
+        .as_array()
+        .context("the notebook has no `cells`")?;
+
+    let mut embedded = EmbeddedCode {
+        code: String::new(),
+        origins: vec![],
+    };
+    for (i, cell) in cells.iter().enumerate() {
+        if cell["cell_type"] != "code" {
+            continue;
+        }
+
+        let source = match &cell["source"] {
+            serde_json::Value::Array(lines) => {
+                lines.iter().filter_map(|line| line.as_str()).collect()
+            }
+            source => source.as_str().unwrap_or_default().to_string(),
+        };
+        for (n, line) in source.lines().enumerate() {
+            embedded.code.push_str(line);
+            embedded.code.push('\n');
+            embedded.origins.push((i + 1, n + 1));
+        }
+    }
+
+    Ok(embedded)
+}
+
+/// The TODOs of a file [`EMBEDDED_CODE`] applies to, `None` for plain
+/// source. A file whose code can not be extracted is skipped with a warning.
+fn scan_embedded(
+    path: &std::path::Path,
+    content: &str,
+    re: &Regex,
+    cfg: &CliConfig,
+) -> Option<Vec<TodoMatch>> {
+    let extension = path.extension()?;
+    let (_, extract) = EMBEDDED_CODE.iter().find(|(ext, _)| extension == *ext)?;
+
+    let embedded = match extract(content) {
+        Ok(embedded) => embedded,
+        Err(e) => {
+            warn!("skipping `{}`: {:#}", display_path(path), e);
+            return Some(vec![]);
+        }
+    };
+
+    let lines = embedded.code.lines().collect::<Vec<_>>();
+    let mut matches = scan_lines(&lines, 0, path, re, cfg);
+    for m in &mut matches {
+        let (cell, line) = embedded.origins[m.item.line - 1];
+        m.item.cell = Some(cell);
+        m.item.line = line;
+    }
+    Some(matches)
+}
+
+/// Files at least this big are split into line ranges scanned in parallel,
+/// so a single huge generated file does not leave the other threads idle.
+const PARALLEL_SCAN_BYTES: usize = 4 * 1024 * 1024;
+
+/// Scans `lines` in `threads` contiguous ranges and concatenates the
+/// matches in line order, the same result as one [`scan_lines`] pass.
+fn scan_lines_parallel(
+    lines: &[&str],
+    threads: usize,
+    path: &std::path::Path,
+    re: &Regex,
+    cfg: &CliConfig,
+) -> Vec<TodoMatch> {
+    let chunk_len = lines.len().div_ceil(threads.max(1)).max(1);
+
+    thread::scope(|scope| {
+        let handles = lines
+            .chunks(chunk_len)
+            .enumerate()
+            .map(|(n, chunk)| {
+                // each chunk but the first starts one line early, for an
+                // `mrdm:ignore-next-line` right above it
+                let start = n * chunk_len;
+                let context = usize::from(start > 0);
+                let lines = &lines[start - context..start + chunk.len()];
+                scope.spawn(move || {
+                    let mut matches = scan_lines(lines, start - context, path, re, cfg);
+                    matches.retain(|m| m.item.line > start);
+                    matches
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Runs `tasks` on at most `jobs` threads at a time, one thread each when
+/// `None`, and returns their results in task order. With a single job they
+/// run one after the other on the calling thread.
+fn run_jobs<T: Send>(tasks: Vec<impl FnOnce() -> T + Send>, jobs: Option<usize>) -> Vec<T> {
+    let threads = jobs.unwrap_or(tasks.len()).min(tasks.len());
+    if threads <= 1 {
+        return tasks.into_iter().map(|task| task()).collect();
+    }
+
+    let queue = std::sync::Mutex::new(tasks.into_iter().enumerate());
+    let mut results = thread::scope(|scope| {
+        let handles = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let next = queue.lock().unwrap().next();
+                        let Some((i, task)) = next else {
+                            break results;
+                        };
+                        results.push((i, task()));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Keeps a TODO-looking line out of the scan when written at its end, e.g.
+/// in a test fixture. `mrdm:ignore-next-line` does the same for the next.
+const IGNORE_DIRECTIVE: &str = "mrdm:ignore";
+
+/// Whether `line` carries [`IGNORE_DIRECTIVE`], or the line before it the
+/// `-next-line` form.
+fn is_ignored(line: &str, prev: Option<&str>) -> bool {
+    let has_directive = |line: &str, suffix: &str| {
+        line.match_indices(IGNORE_DIRECTIVE).any(|(i, _)| {
+            line[i + IGNORE_DIRECTIVE.len()..]
+                .strip_prefix(suffix)
+                .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '-'))
+        })
+    };
+
+    has_directive(line, "") || prev.is_some_and(|prev| has_directive(prev, "-next-line"))
+}
+
+/// Collects the TODOs of `lines`, the first of which is line `offset + 1`
+/// of the file.
+fn scan_lines(
+    lines: &[&str],
+    offset: usize,
+    path: &std::path::Path,
+    re: &Regex,
+    cfg: &CliConfig,
+) -> Vec<TodoMatch> {
+    let mut matches = vec![];
+
+    let markers = cfg
+        .comment_markers
+        .iter()
+        .filter(|marker| !marker.is_empty())
+        .collect::<Vec<_>>();
+
+    // TODO(3): multiline support
+    for (i, line) in lines.iter().enumerate() {
+        // most lines have no comment at all, skip them before the regex
+        if !markers.iter().any(|marker| line.contains(marker.as_str())) {
+            continue;
+        }
+        if is_ignored(line, i.checked_sub(1).map(|prev| lines[prev])) {
+            continue;
+        }
+
+        if let Some(caps) = re
+            .captures(line)
+            .filter(|caps| !follows_url_scheme(&caps["before"], &caps["marker"]))
+        {
+            let title = trim_title(caps.name("title").unwrap().as_str(), &cfg.title_terminators);
+            let (title, snoozed_until) = split_snooze(title);
+            let (title, labels) = split_labels(&title);
+            let category = caps.name("category").unwrap();
+            let column = line[..category.start()].chars().count() + 1;
+            let category = category.as_str();
+
+            matches.push(TodoMatch {
+                id: caps.name("id").map(|id| id.as_str().to_string()),
+                minted: false,
+                item: TodoItem {
+                    title: title.to_string(),
+                    category: category.to_string(),
+                    path: path.to_path_buf(),
+                    line: offset + i + 1,
+                    column,
+                    done: false,
+                    raw: line.to_string(),
+                    labels,
+                    kept: false,
+                    depends_on: parse_depends(title, &cfg.id_prefix),
+                    author: None,
+                    notes: vec![],
+                    cell: None,
+                    created_at: None,
+                    completed_at: None,
+                    snoozed_until,
+                },
+            });
+        }
+    }
+
+    matches
+}
+
+/// Assign phase: gives every TODO without an id the next free one, in
+/// include, path then line order, so the same tree and config always get
+/// the same ids no matter how the scan threads were scheduled. Ids already
+/// used in the source are never handed out again, even when they are
+/// missing from the data file.
+fn assign_ids(files: &mut [ScannedFile], next_id: usize) {
+    let next_free = files
+        .iter()
+        .flat_map(|file| &file.matches)
+        .filter_map(|m| m.id.as_deref()?.parse::<usize>().ok())
+        .map(|id| id + 1)
+        .max()
+        .unwrap_or(0);
+    let mut next_id = next_id.max(next_free);
+
+    files.sort_by(|a, b| a.order_key().cmp(&b.order_key()));
+
+    let writable = files.iter_mut().filter(|file| !file.scan_only);
+    for m in writable.flat_map(|file| &mut file.matches) {
+        if m.id.is_none() {
+            m.id = Some(next_id.to_string());
+            m.minted = true;
+            next_id += 1;
+        }
+    }
+}
+
+/// Stand-in for [`assign_ids`] that leaves the source alone: TODOs without
+/// an id are numbered `new-1`, `new-2`, ... in the same order, after the
+/// placeholders handed out already.
+fn assign_placeholder_ids(files: &mut [ScannedFile]) {
+    files.sort_by(|a, b| a.order_key().cmp(&b.order_key()));
+
+    let taken = files
+        .iter()
+        .flat_map(|file| &file.matches)
+        .filter(|m| {
+            m.id.as_deref()
+                .is_some_and(|id| id.starts_with(PLACEHOLDER_ID_PREFIX))
+        })
+        .count();
+    let unassigned = files
+        .iter_mut()
+        .flat_map(|file| &mut file.matches)
+        .filter(|m| m.id.is_none());
+
+    for (n, m) in unassigned.enumerate() {
+        m.id = Some(format!("{}-{}", PLACEHOLDER_ID_PREFIX, taken + n + 1));
+    }
+}
+
+/// Stand-in for [`assign_ids`] with `tracking: hash`: TODOs without an id
+/// are keyed by [`content_hash`]. The same TODO repeated in a file gets
+/// `-2`, `-3`, ... appended, in line order.
+fn assign_hash_ids(files: &mut [ScannedFile]) {
+    let mut seen = HashMap::new();
+    for file in files.iter_mut() {
+        let path = display_path(normalize_path(&file.path));
+        for m in file.matches.iter_mut().filter(|m| m.id.is_none()) {
+            let hash = content_hash(&m.item.title, &path);
+            let count = seen.entry(hash.clone()).or_insert(0);
+            *count += 1;
+            m.id = Some(match *count {
+                1 => hash,
+                n => format!("{}-{}", hash, n),
+            });
+        }
+    }
+}
+
+/// `h` and 12 hex digits of the FNV-1a hash of the title, lowercased with
+/// its whitespace collapsed, and the path. Unlike `DefaultHasher` it is the
+/// same on every platform and Rust version, and it never parses as a
+/// numeric id.
+fn content_hash(title: &str, path: &str) -> String {
+    let title = title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let hash = title
+        .bytes()
+        .chain([0])
+        .chain(path.bytes())
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("h{:012x}", hash >> 16)
+}
+
+/// Write phase: injects the ids minted for `file` into the source.
+/// Files without new ids are left untouched, and so are read-only ones,
+/// see [`rewrite_lines`].
+fn write_back(
+    file: &ScannedFile,
+    root: &std::path::Path,
+    re: &Regex,
+    delimiters: &IdDelimiters,
+) -> Result<bool> {
+    let minted = minted_lines(file);
+
+    if minted.is_empty() {
+        return Ok(false);
+    }
+
+    rewrite_lines(file, root, |i, line| {
+        let id = minted.get(&i)?;
+
+        Some(inject_id(line, id, re, delimiters))
+    })
+}
+
+/// The unified diff of what [`write_back`] would write to `file`, `None`
+/// when it would leave the file as it is.
+fn preview_write_back(
+    file: &ScannedFile,
+    re: &Regex,
+    delimiters: &IdDelimiters,
+) -> Result<Option<String>> {
+    let minted = minted_lines(file);
+
+    if minted.is_empty() || file.scan_only {
+        return Ok(None);
+    }
+
+    let rewritten = rewritten_content(file, |i, line| {
+        let id = minted.get(&i)?;
+
+        Some(inject_id(line, id, re, delimiters))
+    })?;
+    let rewritten = match &file.encoded {
+        Some((encoding, _)) => encoding.decode(&rewritten).0.into_owned(),
+        None => String::from_utf8_lossy(&rewritten).into_owned(),
+    };
+
+    let path = display_path(&file.path);
+    Ok(Some(
+        similar::TextDiff::from_lines(&file.content, &rewritten)
+            .unified_diff()
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string(),
+    ))
+}
+
+/// The ids minted for `file`, by the 0-based index of their line.
+fn minted_lines(file: &ScannedFile) -> HashMap<usize, &str> {
+    file.matches
+        .iter()
+        .filter(|m| m.minted)
+        .filter_map(|m| Some((m.item.line - 1, m.id.as_deref()?)))
+        .collect()
+}
+
+/// `line` with `id` written after its category.
+fn inject_id(line: &str, id: &str, re: &Regex, delimiters: &IdDelimiters) -> String {
+    let replacement = format!(
+        "${{before}}${{marker}} ${{category}}{}: ${{title}}",
+        delimiters.wrap(id).replace('$', "$$")
+    );
+
+    re.replace(line, replacement).into_owned()
+}
+
+/// The controlling terminal, for prompts that still work when stdin is
+/// redirected or piped. `None` without one, e.g. in CI.
+fn open_tty() -> Option<BufReader<std::fs::File>> {
+    let path = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+    std::fs::File::open(path).ok().map(BufReader::new)
+}
+
+/// Reads the answer to a prompt from [`open_tty`], `None` without a
+/// terminal or when it is closed.
+fn read_tty_line() -> Result<Option<String>> {
+    use std::io::BufRead;
+
+    let Some(mut tty) = open_tty() else {
+        return Ok(None);
+    };
+    let mut answer = String::new();
+    if tty.read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(answer))
+}
+
+/// `--confirm-each`: shows the lines each file would get ids injected into
+/// and asks before doing it, reading each answer with `read_answer`, like
+/// [`read_tty_line`]. Declined files are left as they are, their new TODOs
+/// get placeholder ids.
+fn confirm_injections(
+    files: &mut [ScannedFile],
+    re: &Regex,
+    delimiters: &IdDelimiters,
+    mut read_answer: impl FnMut() -> Result<Option<String>>,
+) -> Result<()> {
+    let mut declined = 0;
+
+    for file in files.iter_mut() {
+        if !file.matches.iter().any(|m| m.minted) {
+            continue;
+        }
+
+        eprintln!("{}", display_path(&file.path));
+        for m in file.matches.iter().filter(|m| m.minted) {
+            let id = m.id.as_deref().unwrap();
+            eprintln!("{}:- {}", m.item.line, m.item.raw);
+            eprintln!(
+                "{}:+ {}",
+                m.item.line,
+                inject_id(&m.item.raw, id, re, delimiters)
+            );
+        }
+
+        let accepted = loop {
+            eprint!("Inject these ids? (y/n) ");
+            let Some(answer) = read_answer()? else {
+                break false;
+            };
+            match answer.trim() {
+                "y" | "Y" => break true,
+                "n" | "N" => break false,
+                _ => continue,
+            }
+        };
+
+        if !accepted {
+            for m in file.matches.iter_mut().filter(|m| m.minted) {
+                declined += 1;
+                m.minted = false;
+                m.id = Some(format!("{}-{}", PLACEHOLDER_ID_PREFIX, declined));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The lines of `file` with their id removed, `// TODO(3): x` becoming
+/// `// TODO: x`, by 0-based line index.
+fn strip_id_lines(file: &ScannedFile, re: &Regex) -> HashMap<usize, String> {
+    file.matches
+        .iter()
+        .filter(|m| m.id.is_some())
+        .map(|m| {
+            let stripped = re.replace(&m.item.raw, "$before$marker $category: $title");
+            (m.item.line - 1, stripped.into_owned())
+        })
+        .collect()
+}
+
+/// The content of `file` with the lines for which `rewrite` returns a new
+/// one replaced, given their 0-based index, in the encoding it was read in.
+fn rewritten_content(
+    file: &ScannedFile,
+    mut rewrite: impl FnMut(usize, &str) -> Option<String>,
+) -> Result<Vec<u8>> {
+    let mut content = Vec::with_capacity(file.content.len());
+
+    // the encoding keeps ASCII as it is, so the lines of the bytes are
+    // those of the decoded content
+    let mut original = file
+        .encoded
+        .as_ref()
+        .map(|(encoding, bytes)| (*encoding, bytes.split_inclusive(|&b| b == b'\n')));
+
+    // keep every line ending as it was, including a missing one at the end
+    for (i, segment) in file.content.split_inclusive('\n').enumerate() {
+        let line = segment.trim_end_matches(['\n', '\r']);
+        let ending = &segment[line.len()..];
+
+        let Some((encoding, original)) = original.as_mut() else {
+            match rewrite(i, line) {
+                Some(line) => {
+                    content.extend_from_slice(line.as_bytes());
+                    content.extend_from_slice(ending.as_bytes());
+                }
+                None => content.extend_from_slice(segment.as_bytes()),
+            }
+            continue;
+        };
+
+        // untouched lines keep their bytes, even those that did not decode
+        let bytes = original.next().unwrap_or_default();
+        match rewrite(i, line) {
+            Some(line) => {
+                let (encoded, _, unmappable) = encoding.encode(&line);
+                if unmappable {
+                    return Err(anyhow::anyhow!(
+                        "line {} of `{}` can not be written in {}",
+                        i + 1,
+                        display_path(&file.path),
+                        encoding.name()
+                    ));
+                }
+                content.extend_from_slice(&encoded);
+                content.extend_from_slice(ending.as_bytes());
+            }
+            None => content.extend_from_slice(bytes),
+        }
+    }
+
+    Ok(content)
+}
+
+/// Writes `file` back through a temp file, replacing the lines for which
+/// `rewrite` returns a new one, given their 0-based index. A read-only file,
+/// or one whose directory is, is skipped with a warning: `false` tells the
+/// caller it is unchanged.
+fn rewrite_lines(
+    file: &ScannedFile,
+    root: &std::path::Path,
+    rewrite: impl FnMut(usize, &str) -> Option<String>,
+) -> Result<bool> {
+    let path = root.join(&file.path);
+    let path = path.as_path();
+    if file.scan_only {
+        warn!(
+            "skipping `{}`, its TODOs can not be rewritten",
+            display_path(path)
+        );
+        return Ok(false);
+    }
+    let skip = |reason: &dyn std::fmt::Display| {
+        warn!("skipping read-only `{}`: {}", display_path(path), reason);
+        Ok(false)
+    };
+
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().readonly()) {
+        return skip(&"the file is not writable");
+    }
+
+    let content = rewritten_content(file, rewrite)?;
+    let mut content_rewritten_buffer = match std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path.with_extension("tmp"))
+    {
+        Ok(buffer) => buffer,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return skip(&e),
+        Err(e) => {
+            return Err(e).with_context(|| format!("could not open file `{}`", &path.display()))
+        }
+    };
+
+    content_rewritten_buffer
+        .write_all(&content)
+        .with_context(|| format!("could not write to temp file for `{}`", path.display()))?;
+
+    // overwrite the original file with the rewritten content
+    match std::fs::rename(path.with_extension("tmp"), path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            let _ = std::fs::remove_file(path.with_extension("tmp"));
+            skip(&e)
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!(
+                "could not rename file `{}` to `{}`",
+                &path.with_extension("tmp").display(),
+                &path.display()
+            )
+        }),
+    }
+}
+
+/// The regex matching a TODO comment of one of `patterns`, e.g. `TODO` or
+/// `FIXME`, after one of the comment `markers`, with ids attached the
+/// `delimiters` way.
+pub fn create_regex(
+    patterns: Vec<&str>,
+    markers: &[String],
+    delimiters: &IdDelimiters,
+    ignore_case: bool,
+) -> Result<Regex> {
+    // an empty alternation would match every comment as an empty category
+    if patterns.is_empty() {
+        return Err(anyhow::anyhow!("no patterns configured"));
+    }
+    if patterns.iter().any(|pattern| pattern.trim().is_empty()) {
+        return Err(anyhow::anyhow!(
+            "no patterns configured, patterns must not contain an empty string"
+        ));
+    }
+
+    // patterns and markers are literals, longest first so `TODO-SEC` is
+    // never cut short to `TODO`
+    let alternatives = |literals: Vec<&str>, boundary: bool| {
+        let mut literals = literals
+            .into_iter()
+            .filter(|literal| !literal.is_empty())
+            .collect::<Vec<_>>();
+        literals.sort_by_key(|literal| std::cmp::Reverse(literal.len()));
+        literals
+            .into_iter()
+            .map(|literal| {
+                let ends_in_word = literal.ends_with(|c: char| c.is_alphanumeric() || c == '_');
+                if boundary && ends_in_word {
+                    format!(r"{}\b", regex::escape(literal))
+                } else {
+                    regex::escape(literal)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+
+    // only whole words are categories, `// TODOey:` is no TODO. Not with a
+    // delimiter like `_` though, the boundary would reject `TODO_3:`
+    let boundary = !delimiters
+        .open
+        .starts_with(|c: char| c.is_alphanumeric() || c == '_');
+    let categories = alternatives(patterns.clone(), boundary);
+    let categories = if ignore_case {
+        format!("(?i:{})", categories)
+    } else {
+        categories
+    };
+
+    let markers = alternatives(with_doc_markers(markers), false);
+    if markers.is_empty() {
+        return Err(anyhow::anyhow!("comment_markers must not be empty"));
+    }
+
+    todo_regex(&markers, &categories, delimiters).with_context(|| {
+        format!(
+            "could not create regex from pattern `{}`",
+            patterns.join("|")
+        )
+    })
+}
+
+/// The comment markers, plus the `///` and `//!` of doc comments when `//`
+/// is one of them, so `/// TODO: x` keeps all three slashes as its marker.
+fn with_doc_markers(markers: &[String]) -> Vec<&str> {
+    let mut markers = markers.iter().map(String::as_str).collect::<Vec<_>>();
+    if markers.contains(&"//") {
+        markers.extend(["///", "//!"]);
+    }
+    markers
+}
+
+/// The regex of a TODO comment, given the alternatives of its markers and
+/// categories as regexes.
+fn todo_regex(markers: &str, categories: &str, delimiters: &IdDelimiters) -> Result<Regex> {
+    // `before` skips over string literals, escaped quotes included, and
+    // char literals like `'"'`, so a marker inside either is not a comment.
+    // It is lazy so the longest marker at the start of a comment wins, the
+    // `///` of a doc comment rather than a `/` before `//`
+    Ok(Regex::new(&format!(
+        r#"^(?<before>(?:"(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)'|[^"])*?)(?<marker>{})\s*(?<category>{})({}{}(?<id>\d+){})?:\s*(?<title>.*)"#,
+        markers,
+        categories,
+        regex::escape(&delimiters.open),
+        if delimiters.prefix.is_empty() {
+            String::new()
+        } else {
+            format!("(?:{})?", regex::escape(&delimiters.prefix))
+        },
+        regex::escape(&delimiters.close)
+    ))?)
+}
+
+/// Whether a marker after `before` is the `//` of a URL like `https://`
+/// rather than a comment.
+fn follows_url_scheme(before: &str, marker: &str) -> bool {
+    let Some(scheme) = before.strip_suffix(':') else {
+        return false;
+    };
+    let start = scheme
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || "+.-".contains(c)))
+        .map_or(0, |i| i + 1);
+    let scheme = &scheme[start..];
+
+    marker.starts_with('/') && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+/// Describes each dependency of the `scanned` items on an id that is done
+/// in `tracked`, or that neither the scan nor `tracked` knows.
+fn dependency_problems(
+    scanned: &[(&str, &TodoItem)],
+    tracked: &HashMap<String, TodoItem>,
+) -> Vec<String> {
+    let mut problems = vec![];
+
+    for (id, item) in scanned {
+        for dep in &item.depends_on {
+            let problem = match tracked.get(dep) {
+                Some(dep_item) if dep_item.done => "which is already done",
+                None if !scanned.iter().any(|(other, _)| other == dep) => "which does not exist",
+                _ => continue,
+            };
+            problems.push(format!(
+                "{}:{}: {}({}) depends on #{}, {}",
+                display_path(&item.path),
+                item.line,
+                item.category,
+                id,
+                dep,
+                problem
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Comments that look like a TODO, `// WORD:` with an uppercase WORD,
+/// whatever the configured patterns, to catch typos like `// TDOO:` that
+/// the scan itself never matches. Returns the file index, the 1-based line
+/// and the category of those outside `allowed_categories`.
+fn disallowed_categories(
+    files: &[ScannedFile],
+    cfg: &CliConfig,
+) -> Result<Vec<(usize, usize, String)>> {
+    if cfg.allowed_categories.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut markers = with_doc_markers(&cfg.comment_markers)
+        .into_iter()
+        .filter(|marker| !marker.is_empty())
+        .map(regex::escape)
+        .collect::<Vec<_>>();
+    markers.sort_by_key(|marker| std::cmp::Reverse(marker.len()));
+    let re = todo_regex(&markers.join("|"), "[A-Z][A-Z0-9_-]*", &cfg.delimiters())?;
+
+    let allowed = |category: &str| {
+        cfg.allowed_categories.iter().any(|allowed| {
+            allowed == category || cfg.ignore_case && allowed.eq_ignore_ascii_case(category)
+        })
+    };
+
+    let mut found = vec![];
+    for (index, file) in files.iter().enumerate() {
+        let mut prev = None;
+        for (i, line) in file.content.lines().enumerate() {
+            let ignored = is_ignored(line, prev.replace(line));
+            let Some(caps) = re.captures(line).filter(|_| !ignored) else {
+                continue;
+            };
+            if follows_url_scheme(&caps["before"], &caps["marker"]) {
+                continue;
+            }
+
+            let category = caps.name("category").unwrap().as_str();
+            if !allowed(category) {
+                found.push((index, i + 1, category.to_string()));
+            }
+        }
+    }
+
+    Ok(found)
+}
+
+/// Per-run knobs of [`get_todos`] that do not come from the config file.
+#[derive(Debug, Clone, Default)]
+struct ScanOptions {
+    /// the first id handed out to a new TODO
+    next_id: usize,
+    /// use placeholder ids instead of minting and injecting real ones
+    no_rewrite: bool,
+    /// totals accumulated by the scan threads
+    stats: Arc<ScanStats>,
+    /// the items of the data file, to recognize TODOs that lost their id
+    tracked: HashMap<String, TodoItem>,
+    /// fail instead of restoring lost ids
+    strict: bool,
+    /// ignore `max_changes` and `max_new_ids`
+    force: bool,
+    /// only scan these of the included files
+    only: Option<HashSet<std::path::PathBuf>>,
+    /// ask before injecting ids into each file, see [`confirm_injections`]
+    confirm_each: bool,
+    /// mint ids but keep them out of the files, collecting
+    /// [`ScanStats::previews`] instead
+    dry_run: bool,
+    /// the most threads to scan and rewrite files on, see [`run_jobs`]
+    jobs: Option<usize>,
+}
+
+/// What a scan read and wrote, shared between the scan threads.
+#[derive(Debug, Default)]
+struct ScanStats {
+    files: AtomicUsize,
+    lines: AtomicUsize,
+    bytes: AtomicUsize,
+    /// files that got ids injected
+    rewritten: std::sync::Mutex<Vec<std::path::PathBuf>>,
+    /// the ids injected into them
+    minted: std::sync::Mutex<Vec<String>>,
+    /// with [`ScanOptions::dry_run`], the diff of each file that would get
+    /// ids
+    previews: std::sync::Mutex<Vec<String>>,
+}
+
+impl ScanStats {
+    fn report(&self, elapsed: std::time::Duration) -> String {
+        format!(
+            "scanned {} files, {} lines, {} bytes in {:.2?}",
+            self.files.load(Ordering::Relaxed),
+            self.lines.load(Ordering::Relaxed),
+            self.bytes.load(Ordering::Relaxed),
+            elapsed
+        )
+    }
+}
+
+/// Scans the files `config` includes for its `patterns`, leaving both the
+/// files and the data file untouched. TODOs without an id are keyed by a
+/// `new-N` placeholder.
+pub fn scan(config: &CliConfig) -> Result<TodoList> {
+    let options = ScanOptions {
+        no_rewrite: true,
+        ..Default::default()
+    };
+    Ok(TodoList::new(get_todos(&[], None, config, &options)?))
+}
+
+/// Scans the configured files in three phases: a parallel read-only scan,
+/// a single-threaded id assignment, and a parallel write-back of the files
+/// that received new ids.
+fn get_todos(
+    pattern: &[String],
+    path: Option<std::path::PathBuf>,
+    cfg: &CliConfig,
+    options: &ScanOptions,
+) -> Result<HashMap<String, TodoItem>> {
+    let (re, mut files) = scan_files(pattern, path, cfg, options)?;
+
+    let lost = match cfg.tracking {
+        Tracking::Id => find_lost_ids(&files, &options.tracked),
+        // hashes never make it into the source, so they can't get lost
+        Tracking::Hash => vec![],
+    };
+    if !lost.is_empty() {
+        if options.strict {
+            for lost in &lost {
+                warn!("{}", lost.describe(&files));
+            }
+            return Err(anyhow::anyhow!(
+                "{} tracked TODOs lost their id, run `mrdm todo check --fix` to restore them",
+                lost.len()
+            ));
+        }
+
+        for lost in &lost {
+            warn!("{}, restoring it", lost.describe(&files));
+        }
+        restore_lost_ids(&mut files, &lost, !options.no_rewrite);
+    }
+
+    match cfg.tracking {
+        Tracking::Hash => assign_hash_ids(&mut files),
+        Tracking::Id if options.no_rewrite => assign_placeholder_ids(&mut files),
+        Tracking::Id => {
+            assign_ids(&mut files, options.next_id);
+            // a dry run writes nothing, there is nothing to confirm
+            if options.confirm_each && !options.dry_run {
+                confirm_injections(&mut files, &re, &cfg.delimiters(), read_tty_line)?;
+            }
+            // the TODOs of scan-only files
+            assign_placeholder_ids(&mut files);
+        }
+    }
+
+    if !options.force {
+        check_change_budget(&files, cfg)?;
+    }
+
+    let mut placeholders = files
+        .iter()
+        .flat_map(|file| &file.matches)
+        .filter(|m| {
+            m.id.as_deref()
+                .is_some_and(|id| id.starts_with(PLACEHOLDER_ID_PREFIX))
+        })
+        .count();
+    let tasks = files
+        .into_iter()
+        .map(|file| {
+            let re = Arc::clone(&re);
+            let delimiters = cfg.delimiters();
+            let (dry_run, stats) = (options.dry_run, Arc::clone(&options.stats));
+            let root = cfg.root.clone();
+            move || {
+                if dry_run {
+                    let preview = preview_write_back(&file, &re, &delimiters)?;
+                    let written = preview.is_some();
+                    stats.previews.lock().unwrap().extend(preview);
+                    return Ok((written, file));
+                }
+                write_back(&file, &root, &re, &delimiters).map(|written| (written, file))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut todo_items = HashMap::new();
+    let mut minted = vec![];
+
+    for result in run_jobs(tasks, options.jobs) {
+        let (written, mut file) = result?;
+        if written {
+            minted.extend(
+                file.matches
+                    .iter()
+                    .filter(|m| m.minted)
+                    .filter_map(|m| m.id.clone()),
+            );
+            options.stats.rewritten.lock().unwrap().push(file.path);
+        } else {
+            // a skipped read-only file keeps its TODOs untracked, as with
+            // --no-rewrite
+            for m in file.matches.iter_mut().filter(|m| m.minted) {
+                placeholders += 1;
+                m.id = Some(format!("{}-{}", PLACEHOLDER_ID_PREFIX, placeholders));
+            }
+        }
+
+        for m in file.matches {
+            todo_items.insert(m.id.unwrap(), m.item);
+        }
+    }
+
+    if cfg.history && !options.dry_run {
+        let now = chrono::Utc::now();
+        let created = minted
+            .iter()
+            .map(|id| HistoryEntry::new(now, HistoryEvent::Created, id));
+        append_history(&cfg.root.join(HISTORY_PATH), created)?;
+    }
+    options.stats.minted.lock().unwrap().extend(minted);
+
+    Ok(todo_items)
+}
+
+/// Fails before anything is written when the ids about to be injected go
+/// over `max_changes` files or `max_new_ids` ids.
+fn check_change_budget(files: &[ScannedFile], cfg: &CliConfig) -> Result<()> {
+    let minted = files
+        .iter()
+        .map(|file| file.matches.iter().filter(|m| m.minted).count())
+        .filter(|&count| count > 0)
+        .collect::<Vec<_>>();
+    let (changes, new_ids) = (minted.len(), minted.iter().sum::<usize>());
+
+    if let Some(max) = cfg.max_changes.filter(|&max| changes > max) {
+        return Err(anyhow::anyhow!(
+            "this run would rewrite {} files, more than max_changes ({}), pass --force to do it anyway",
+            changes,
+            max
+        ));
+    }
+    if let Some(max) = cfg.max_new_ids.filter(|&max| new_ids > max) {
+        return Err(anyhow::anyhow!(
+            "this run would inject {} new ids, more than max_new_ids ({}), pass --force to do it anyway",
+            new_ids,
+            max
+        ));
+    }
+
+    Ok(())
+}
+
+/// Scans the files of a tarball the include globs select, without
+/// extracting it. Gzipped when the name ends in `gz`. Files that are not
+/// in the configured `encoding` are skipped.
+fn scan_archive(
+    archive: &std::path::Path,
+    pattern: &[String],
+    cfg: &CliConfig,
+) -> Result<Vec<ScannedFile>> {
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("could not open archive `{}`", display_path(archive)))?;
+    let reader: Box<dyn std::io::Read> = if archive.to_string_lossy().ends_with("gz") {
+        Box::new(flate2::read::GzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    scan_tar(reader, archive, pattern, cfg)
+}
+
+/// [`scan_archive`] of the uncompressed tar read from `reader`.
+fn scan_tar(
+    reader: impl std::io::Read,
+    archive: &std::path::Path,
+    pattern: &[String],
+    cfg: &CliConfig,
+) -> Result<Vec<ScannedFile>> {
+    let patterns = resolve_pattern(pattern, cfg);
+    let re = create_regex(
+        patterns.iter().map(String::as_str).collect(),
+        &cfg.comment_markers,
+        &cfg.delimiters(),
+        cfg.ignore_case,
+    )?;
+
+    let includes = cfg
+        .include
+        .iter()
+        .enumerate()
+        .flat_map(|(root, include)| {
+            expand_braces(include)
+                .into_iter()
+                .map(move |include| Ok((root, glob::Pattern::new(&include)?)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let read_error = || format!("could not read archive `{}`", display_path(archive));
+    let mut tar = tar::Archive::new(reader);
+    let mut files = vec![];
+
+    for entry in tar.entries().with_context(read_error)? {
+        let mut entry = entry.with_context(read_error)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = normalize_path(&entry.path().with_context(read_error)?).to_path_buf();
+        let Some(root) = includes
+            .iter()
+            .find(|(_, include)| include.matches_path_with(&path, INCLUDE_MATCH_OPTIONS))
+            .map(|(root, _)| *root)
+        else {
+            continue;
+        };
+        if !has_allowed_extension(&path, &cfg.extensions) {
+            continue;
+        }
+
+        let mut bytes = vec![];
+        std::io::Read::read_to_end(&mut entry, &mut bytes).with_context(read_error)?;
+        let content = match decode_source(bytes, cfg.source_encoding()) {
+            Some(Ok((content, _))) => content,
+            Some(Err(_)) => {
+                debug!(
+                    "skipping file that is not {}: {}",
+                    cfg.source_encoding().name(),
+                    path.display()
+                );
+                continue;
+            }
+            None => {
+                debug!("skipping binary file: {}", path.display());
+                continue;
+            }
+        };
+
+        let (matches, scan_only) = match scan_embedded(&path, &content, &re, cfg) {
+            Some(matches) => (matches, true),
+            None => {
+                let lines = content.lines().collect::<Vec<_>>();
+                (scan_lines(&lines, 0, &path, &re, cfg), false)
+            }
+        };
+        files.push(ScannedFile {
+            path,
+            root,
+            content,
+            scan_only,
+            encoded: None,
+            matches,
+        });
+    }
+
+    files.sort_by(|a, b| a.order_key().cmp(&b.order_key()));
+
+    Ok(files)
+}
+
+/// Runs [`scan_file`] on every file the pattern and include globs select,
+/// in parallel up to [`ScanOptions::jobs`], and returns the results sorted
+/// by path.
+fn scan_files(
+    pattern: &[String],
+    path: Option<std::path::PathBuf>,
+    cfg: &CliConfig,
+    options: &ScanOptions,
+) -> Result<(Arc<Regex>, Vec<ScannedFile>)> {
+    let patterns = resolve_pattern(pattern, cfg);
+
+    let re = Arc::new(create_regex(
+        patterns.iter().map(String::as_str).collect(),
+        &cfg.comment_markers,
+        &cfg.delimiters(),
+        cfg.ignore_case,
+    )?);
+    let cfg = Arc::new(cfg.clone());
+
+    let paths = if let Some(path) = path {
+        vec![path]
+    } else {
+        let mut paths = cfg
+            .include
+            .iter()
+            .map(std::path::PathBuf::from)
+            .collect::<Vec<_>>();
+        if let Some(list) = &cfg.include_from {
+            paths.extend(read_path_list(&cfg.root.join(list))?);
+        }
+        paths
+    };
+
+    let mut tasks = vec![];
+    let mut seen = HashSet::new();
+
+    let patterns = paths
+        .iter()
+        .enumerate()
+        .flat_map(|(root, path)| {
+            expand_braces(&path.to_string_lossy())
+                .into_iter()
+                .map(move |pattern| (root, pattern))
+        })
+        .collect::<Vec<_>>();
+
+    // files each include entry matched, to catch typos that scan nothing
+    let mut matched = vec![0; paths.len()];
+    let mut invalid = HashSet::new();
+
+    for (root, pattern) in patterns {
+        // a typo in one entry must not stop the others from being scanned
+        // globs match under `--root`, the paths they yield stay relative to it
+        let at_root = std::path::Path::new(&glob::Pattern::escape(&cfg.root.to_string_lossy()))
+            .join(&pattern);
+        let entries = match glob::glob_with(&at_root.to_string_lossy(), INCLUDE_MATCH_OPTIONS) {
+            Ok(entries) => entries,
+            Err(e) => {
+                let message = format!("invalid include pattern `{}`: {}", pattern, e);
+                if options.strict {
+                    return Err(anyhow::anyhow!(message));
+                }
+                warn!("{}, skipping it", message);
+                invalid.insert(root);
+                continue;
+            }
+        };
+
+        for entry in entries {
+            let path = match entry {
+                Ok(path) => path
+                    .strip_prefix(&cfg.root)
+                    .map_or_else(|_| path.clone(), std::path::Path::to_path_buf),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    continue;
+                }
+            };
+
+            if !has_allowed_extension(&path, &cfg.extensions) {
+                debug!("skipping file: {}", path.display());
+                continue;
+            }
+
+            matched[root] += 1;
+
+            if options
+                .only
+                .as_ref()
+                .is_some_and(|only| !only.contains(normalize_path(&path)))
+            {
+                debug!("skipping file: {}", path.display());
+                continue;
+            }
+
+            // overlapping globs must not scan, and later rewrite, a file twice
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+
+            let re = Arc::clone(&re);
+            let cfg = Arc::clone(&cfg);
+            let stats = Arc::clone(&options.stats);
+            let jobs = options.jobs;
+            debug!("processing file: {}", path.display());
+            tasks.push(move || {
+                scan_file(&path, &re, &cfg, &stats, jobs).map(|file| ScannedFile { root, ..file })
+            });
+        }
+    }
+
+    let unmatched = paths
+        .iter()
+        .zip(&matched)
+        .enumerate()
+        .filter(|(root, (_, count))| **count == 0 && !invalid.contains(root))
+        .map(|(_, entry)| entry)
+        .map(|(path, _)| format!("`{}`", display_path(path)))
+        .collect::<Vec<_>>();
+
+    if !unmatched.is_empty() {
+        let message = format!(
+            "include patterns matched no files: {}",
+            unmatched.join(", ")
+        );
+        if options.strict {
+            return Err(anyhow::anyhow!(message));
+        }
+        warn!("{}", message);
+    }
+
+    let mut files = run_jobs(tasks, options.jobs)
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+    files.sort_by(|a, b| a.order_key().cmp(&b.order_key()));
+
+    Ok((re, files))
+}
+
+/// Expands `{a,b}` alternatives, which `glob` does not support, into one
+/// pattern each: `src/*.{rs,toml}` becomes `src/*.rs` and `src/*.toml`.
+/// Braces nest, and an unmatched or comma-less brace is kept as written.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+
+    let mut depth = 0;
+    let mut commas = vec![];
+    let mut close = None;
+
+    for (i, c) in pattern[open..].char_indices().map(|(i, c)| (open + i, c)) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(i);
+                    break;
+                }
+            }
+            ',' if depth == 1 => commas.push(i),
+            _ => {}
+        }
+    }
+
+    let Some(close) = close else {
+        return vec![pattern.to_string()];
+    };
+
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+
+    if commas.is_empty() {
+        // nothing to choose from, keep `{x}` and expand what follows
+        return expand_braces(suffix)
+            .into_iter()
+            .map(|rest| format!("{}{}", &pattern[..=close], rest))
+            .collect();
+    }
+
+    let mut bounds = vec![open];
+    bounds.extend(&commas);
+    bounds.push(close);
+
+    bounds
+        .windows(2)
+        .flat_map(|w| expand_braces(&format!("{}{}{}", prefix, &pattern[w[0] + 1..w[1]], suffix)))
+        .collect()
+}
+
+/// Reads an `include_from` list: one path or glob per line, skipping blank
+/// lines and `#` comments.
+fn read_path_list(list: &std::path::Path) -> Result<Vec<std::path::PathBuf>> {
+    let content = std::fs::read_to_string(list)
+        .with_context(|| format!("could not read path list `{}`", display_path(list)))?;
+
+    Ok(parse_path_list(&content))
+}
+
+fn parse_path_list(content: &str) -> Vec<std::path::PathBuf> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(std::path::PathBuf::from)
+        .collect()
+}
+
+/// The `-p` patterns given on the command line, else the configured ones,
+/// each once and in the order first given.
+fn resolve_pattern(pattern: &[String], cfg: &CliConfig) -> Vec<String> {
+    let pattern = if pattern.is_empty() {
+        &cfg.patterns
+    } else {
+        pattern
+    };
+
+    let mut seen = HashSet::new();
+    pattern
+        .iter()
+        .filter(|pattern| seen.insert(pattern.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// A TODO written without an id whose tracked item still has one, e.g.
+/// after `// TODO(3): x` was edited back to `// TODO: x`.
+#[derive(Debug, Clone, PartialEq)]
+struct LostId {
+    /// index into the scanned files
+    file: usize,
+    /// index into the matches of that file
+    index: usize,
+    /// the id the item is tracked under
+    id: String,
+}
+
+impl LostId {
+    fn describe(&self, files: &[ScannedFile]) -> String {
+        let m = &files[self.file].matches[self.index];
+
+        format!(
+            "{}:{}: {} `{}` lost its id {}",
+            display_path(&m.item.path),
+            m.item.line,
+            m.item.category,
+            m.item.title,
+            self.id
+        )
+    }
+}
+
+/// Pairs TODOs without an id with tracked items of the same path and
+/// title whose id appears nowhere in the source anymore. Titles that are
+/// not unique on either side are left alone rather than guessed.
+fn find_lost_ids(files: &[ScannedFile], tracked: &HashMap<String, TodoItem>) -> Vec<LostId> {
+    let in_source = files
+        .iter()
+        .flat_map(|file| &file.matches)
+        .filter_map(|m| m.id.as_deref())
+        .collect::<HashSet<_>>();
+
+    let mut candidates = HashMap::<_, Vec<&str>>::new();
+    for (id, item) in tracked {
+        if !in_source.contains(id.as_str()) {
+            candidates
+                .entry((normalize_path(&item.path), item.title.as_str()))
+                .or_default()
+                .push(id);
+        }
+    }
+
+    let mut unassigned = HashMap::<_, Vec<(usize, usize)>>::new();
+    for (f, file) in files.iter().enumerate() {
+        for (i, m) in file.matches.iter().enumerate() {
+            if m.id.is_none() {
+                unassigned
+                    .entry((normalize_path(&file.path), m.item.title.as_str()))
+                    .or_default()
+                    .push((f, i));
+            }
+        }
+    }
+
+    let mut lost = unassigned
+        .into_iter()
+        .filter_map(
+            |(key, at)| match (candidates.get(&key)?.as_slice(), at.as_slice()) {
+                ([id], [(file, index)]) => Some(LostId {
+                    file: *file,
+                    index: *index,
+                    id: id.to_string(),
+                }),
+                _ => None,
+            },
+        )
+        .collect::<Vec<_>>();
+
+    lost.sort_by_key(|lost| (lost.file, lost.index));
+
+    lost
+}
+
+/// Puts the tracked ids back on their TODOs, marking them for
+/// [`write_back`] when `rewrite` is set.
+fn restore_lost_ids(files: &mut [ScannedFile], lost: &[LostId], rewrite: bool) {
+    for lost in lost {
+        let m = &mut files[lost.file].matches[lost.index];
+        m.id = Some(lost.id.clone());
+        m.minted = rewrite;
+    }
+}
+
+/// Numeric ids first and in numeric order, then everything else by name.
+fn id_sort_key(id: &str) -> ((u8, usize), String) {
+    (
+        id.parse::<usize>().map_or((1, 0), |n| (0, n)),
+        id.to_string(),
+    )
+}
+
+/// Sorts items by id, see [`id_sort_key`].
+fn sort_todo_items(items: impl IntoIterator<Item = (String, TodoItem)>) -> Vec<(String, TodoItem)> {
+    let mut items = items.into_iter().collect::<Vec<_>>();
+
+    items.sort_by_key(|(id, _)| id_sort_key(id));
+
+    items
+}
+
+/// What makes two TODOs duplicates for `--dedupe`: their category and
+/// title, lowercased when categories match in any case.
+fn dedupe_key(item: &TodoItem, ignore_case: bool) -> (String, String) {
+    let (category, title) = (item.category.as_str(), item.title.trim());
+
+    if ignore_case {
+        (category.to_lowercase(), title.to_lowercase())
+    } else {
+        (category.to_string(), title.to_string())
+    }
+}
+
+/// Keeps the first of every group of duplicates, as it was written.
+fn dedupe_todo_items(items: Vec<(String, TodoItem)>, ignore_case: bool) -> Vec<(String, TodoItem)> {
+    let mut seen = HashSet::new();
+
+    items
+        .into_iter()
+        .filter(|(_, item)| seen.insert(dedupe_key(item, ignore_case)))
+        .collect()
+}
+
+/// A scan only finds open TODOs and knows nothing of notes: carries the
+/// notes, and the completion of items that were done with their comment
+/// kept in the source, over from `prev`.
+fn carry_over(curr: &mut HashMap<String, TodoItem>, prev: &HashMap<String, TodoItem>) {
+    for (id, item) in curr.iter_mut() {
+        let Some(prev) = prev.get(id) else {
+            continue;
+        };
+        if prev.done && prev.kept {
+            item.done = true;
+            item.kept = true;
+        }
+        item.notes.clone_from(&prev.notes);
+        item.created_at = prev.created_at;
+        item.completed_at = prev.completed_at;
+    }
+}
+
+/// Stamps items `todo done` is about to save: `created_at` of those that
+/// are not `tracked` yet, and `completed_at` following `done`.
+fn stamp_transitions(
+    items: &mut HashMap<String, TodoItem>,
+    tracked: &HashSet<String>,
+    now: chrono::DateTime<chrono::Utc>,
+) {
+    for (id, item) in items.iter_mut() {
+        if !tracked.contains(id) {
+            item.created_at = Some(now);
+        }
+        if !item.done {
+            item.completed_at = None;
+        } else if item.completed_at.is_none() {
+            item.completed_at = Some(now);
+        }
+    }
+}
+
+/// Whether `item` was created or completed at or after `since`.
+fn is_changed_since(item: &TodoItem, since: chrono::DateTime<chrono::Utc>) -> bool {
+    [item.created_at, item.completed_at]
+        .into_iter()
+        .flatten()
+        .any(|at| at >= since)
+}
+
+/// Parses a duration like `30m`, `24h`, `7d` or `2w` for `--changed-since`.
+fn parse_duration(spec: &str) -> std::result::Result<chrono::TimeDelta, String> {
+    let invalid = || {
+        format!(
+            "invalid duration `{}`, expected e.g. `30m`, `24h`, `7d` or `2w`",
+            spec
+        )
+    };
+
+    let spec = spec.trim();
+    let unit = spec.chars().last().ok_or_else(invalid)?;
+    let count = spec[..spec.len() - unit.len_utf8()]
+        .parse::<i64>()
+        .map_err(|_| invalid())?;
+    let duration = match unit {
+        'm' => chrono::TimeDelta::try_minutes(count),
+        'h' => chrono::TimeDelta::try_hours(count),
+        'd' => chrono::TimeDelta::try_days(count),
+        'w' => chrono::TimeDelta::try_weeks(count),
+        _ => None,
+    };
+    duration.filter(|_| count >= 0).ok_or_else(invalid)
+}
+
+/// Key-level changes between two snapshots of the tracked TODOs.
+#[derive(Debug, Default, PartialEq)]
+struct TodoDiff {
+    /// ids only present in the newer snapshot
+    added: Vec<String>,
+    /// open ids of the older snapshot that are gone from the newer one
+    deleted: Vec<String>,
+    /// ids done in the older snapshot but open in the newer one
+    undone: Vec<String>,
+    /// ids open in the older snapshot and done in the newer one
+    completed: Vec<String>,
+}
+
+fn diff_todos(prev: &HashMap<String, TodoItem>, curr: &HashMap<String, TodoItem>) -> TodoDiff {
+    let mut diff = TodoDiff::default();
+
+    for (id, item) in prev {
+        match curr.get(id) {
+            None if !item.done => diff.deleted.push(id.clone()),
+            Some(curr_item) if item.done && !curr_item.done => diff.undone.push(id.clone()),
+            Some(curr_item) if !item.done && curr_item.done => diff.completed.push(id.clone()),
+            _ => {}
+        }
+    }
+
+    diff.added = curr
+        .keys()
+        .filter(|id| !prev.contains_key(*id))
+        .cloned()
+        .collect();
+
+    for ids in [
+        &mut diff.added,
+        &mut diff.deleted,
+        &mut diff.undone,
+        &mut diff.completed,
+    ] {
+        ids.sort_by_key(|id| id_sort_key(id));
+    }
+
+    diff
+}
+
+/// The transitions `todo done` performed, printed to stderr at the end.
+#[derive(Debug, Default)]
+struct DoneSummary {
+    marked_done: Vec<String>,
+    removed: Vec<String>,
+    reopened: Vec<String>,
+    /// old id, new id
+    recreated: Vec<(String, String)>,
+    new: Vec<String>,
+}
+
+impl DoneSummary {
+    fn render(&self) -> String {
+        let mut parts = vec![];
+
+        for (label, ids) in [
+            ("Marked done", &self.marked_done),
+            ("Removed", &self.removed),
+            ("Reopened", &self.reopened),
+        ] {
+            if !ids.is_empty() {
+                parts.push(format!("{}: {}.", label, format_id_ranges(ids)));
+            }
+        }
+
+        if !self.recreated.is_empty() {
+            let recreated = self
+                .recreated
+                .iter()
+                .map(|(old, new)| format!("{}→{}", old, new))
+                .collect::<Vec<_>>();
+            parts.push(format!("Recreated: {}.", recreated.join(", ")));
+        }
+
+        if !self.new.is_empty() {
+            parts.push(format!("New: {}.", format_id_ranges(&self.new)));
+        }
+
+        if parts.is_empty() {
+            "Nothing changed.".to_string()
+        } else {
+            parts.join(" ")
+        }
+    }
+
+    /// The transitions as history entries, except `new`, which the scan
+    /// logged as it minted their ids.
+    fn history(&self, at: chrono::DateTime<chrono::Utc>) -> Vec<HistoryEntry> {
+        let mut entries = vec![];
+        for (event, ids) in [
+            (HistoryEvent::Done, &self.marked_done),
+            (HistoryEvent::Removed, &self.removed),
+            (HistoryEvent::Undone, &self.reopened),
+        ] {
+            entries.extend(ids.iter().map(|id| HistoryEntry::new(at, event, id)));
+        }
+        entries.extend(self.recreated.iter().map(|(old, new)| HistoryEntry {
+            previous_id: Some(old.clone()),
+            ..HistoryEntry::new(at, HistoryEvent::Reindexed, new)
+        }));
+
+        entries
+    }
+}
+
+/// Joins ids with commas, collapsing runs of consecutive numbers to `a–b`.
+fn format_id_ranges(ids: &[String]) -> String {
+    let mut ids = ids.to_vec();
+    ids.sort_by_key(|id| id_sort_key(id));
+
+    let mut parts: Vec<(String, Option<usize>, Option<String>)> = vec![];
+
+    for id in ids {
+        let number = id.parse::<usize>().ok();
+
+        if let Some((_, Some(last), end)) = parts.last_mut() {
+            if number == Some(*last + 1) {
+                *last += 1;
+                *end = Some(id);
+                continue;
+            }
+        }
+
+        parts.push((id, number, None));
+    }
+
+    parts
+        .into_iter()
+        .map(|(start, _, end)| match end {
+            Some(end) => format!("{}–{}", start, end),
+            None => start,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reads the data file, treating unparsable content as an empty list.
+fn read_data_file(backend: DataBackend, root: &std::path::Path) -> Result<TodoList> {
+    match backend {
+        DataBackend::Json => read_json_data_file(root),
+        DataBackend::Sqlite => sqlite_data::read(root),
+    }
+}
+
+fn read_json_data_file(root: &std::path::Path) -> Result<TodoList> {
+    let path = root.join(OUT_PATH);
+    let data_in = std::fs::OpenOptions::new()
+        .read(true)
+        .open(&path)
+        .with_context(|| format!("could not open file `{}`", display_path(&path)))?;
+    let rdr = BufReader::new(data_in);
+
+    let mut todo = serde_json::from_reader(rdr).unwrap_or_else(|_| TodoList::new(HashMap::new()));
+
+    // only upgraded in memory: read-only commands do not hold the
+    // `DataLock`, the next locked write persists the upgraded form
+    if todo.schema_version != SCHEMA_VERSION {
+        let from = todo.schema_version;
+        todo.migrate()?;
+        debug!(
+            "upgraded `{}` from schema_version {}",
+            display_path(&path),
+            from
+        );
+    }
+
+    Ok(todo)
+}
+
+/// Matches titles containing `query` in any case, or matching it as a
+/// regex for `todo grep --regex`.
+fn title_matcher(query: &str, regex: bool) -> Result<Regex> {
+    let pattern = if regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+
+    regex::RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("invalid regex `{}`", query))
+}
+
+/// The id after the largest numeric id in `items`, or 0 when there is none.
+fn next_id(items: &HashMap<String, TodoItem>) -> usize {
+    items
+        .keys()
+        .filter_map(|id| id.parse::<usize>().ok())
+        .map(|id| id + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Copies the item `id` to the next free id, notes and all, for `todo done`
+/// to recreate an item that is back in the code. Returns the new id.
+fn recreate_item(items: &mut HashMap<String, TodoItem>, id: &str) -> String {
+    let fresh = next_id(items).to_string();
+    let copy = items[id].clone();
+    items.insert(fresh.clone(), copy);
+    fresh
+}
+
+#[derive(Serialize)]
+struct TodoEntry<'a> {
+    id: &'a str,
+    #[serde(flatten)]
+    item: &'a TodoItem,
+}
+
+fn write_todo_json(todo_items: &[(String, TodoItem)], outbuf: &mut impl Write) -> Result<()> {
+    let entries = todo_items
+        .iter()
+        .map(|(id, item)| TodoEntry { id, item })
+        .collect::<Vec<_>>();
+
+    serde_json::to_writer_pretty(&mut *outbuf, &entries).context("could not write JSON output")?;
+    writeln!(outbuf)?;
+
+    Ok(())
+}
+
+/// Renders `--format sarif`: every open item is a result of the rule named
+/// after its category, keyed by its id so code scanning can follow it
+/// across runs.
+fn write_todo_sarif(todo_items: &[(String, TodoItem)], outbuf: &mut impl Write) -> Result<()> {
+    let open = todo_items
+        .iter()
+        .filter(|(_, item)| !item.done)
+        .collect::<Vec<_>>();
+    let categories = open
+        .iter()
+        .map(|(_, item)| item.category.as_str())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let rules = categories
+        .iter()
+        .map(|category| {
+            serde_json::json!({
+                "id": category,
+                "shortDescription": { "text": format!("{} comment", category) },
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = open
+        .iter()
+        .map(|(id, item)| {
+            let mut region = serde_json::json!({ "startLine": item.line });
+            // items tracked before columns were have none
+            if item.column > 0 {
+                region["startColumn"] = item.column.into();
+            }
+            serde_json::json!({
+                "ruleId": item.category,
+                "ruleIndex": categories.binary_search(&item.category.as_str()).unwrap(),
+                "level": "note",
+                "message": { "text": item.title.trim() },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": display_path(normalize_path(&item.path)) },
+                        "region": region,
+                    },
+                }],
+                "partialFingerprints": { "mrdmId": id },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let log = serde_json::json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "mrdm",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    });
+    serde_json::to_writer_pretty(&mut *outbuf, &log).context("could not write SARIF output")?;
+    writeln!(outbuf)?;
+
+    Ok(())
+}
+
+/// Every field of a JSON entry, in the order they are emitted by default.
+const TODO_FIELDS: [&str; 17] = [
+    "id",
+    "title",
+    "category",
+    "path",
+    "line",
+    "column",
+    "done",
+    "raw",
+    "labels",
+    "kept",
+    "depends_on",
+    "author",
+    "notes",
+    "cell",
+    "created_at",
+    "completed_at",
+    "snoozed_until",
+];
+
+/// Checks `--fields` against [`TODO_FIELDS`], keeping the given order.
+/// Tables can also show the `location` column, see [`TABLE_COLUMNS`].
+fn parse_fields(fields: &[String], format: OutputFormat) -> Result<Vec<&'static str>> {
+    let known = match format {
+        OutputFormat::Json | OutputFormat::Csv => TODO_FIELDS.to_vec(),
+        OutputFormat::MarkdownTable => TODO_FIELDS.iter().copied().chain(["location"]).collect(),
+        _ if fields.is_empty() => return Ok(vec![]),
+        _ => {
+            return Err(anyhow::anyhow!(
+                "--fields only applies to --format json, markdown-table, csv and --null"
+            ))
+        }
+    };
+
+    fields
+        .iter()
+        .map(|field| {
+            known
+                .iter()
+                .find(|known| **known == field.trim())
+                .copied()
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "unknown field `{}`, expected one of {}",
+                        field,
+                        known.join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+/// The columns of `--format markdown-table` without `--fields`.
+const TABLE_COLUMNS: [&str; 5] = ["done", "category", "id", "title", "location"];
+
+/// Renders `--format markdown-table` with the given columns, or
+/// [`TABLE_COLUMNS`] when there are none.
+fn write_todo_table(
+    todo_items: &[(String, TodoItem)],
+    fields: &[&'static str],
+    render: &RenderOptions,
+    is_stdout: bool,
+    outbuf: &mut impl Write,
+) -> Result<()> {
+    let fields = if fields.is_empty() {
+        &TABLE_COLUMNS[..]
+    } else {
+        fields
+    };
+
+    let headers = fields
+        .iter()
+        .map(|field| match *field {
+            "done" => "Status".to_string(),
+            "id" => "ID".to_string(),
+            field => {
+                let mut header = field.replace('_', " ");
+                header[..1].make_ascii_uppercase();
+                header
+            }
+        })
+        .collect::<Vec<_>>();
+    writeln!(outbuf, "| {} |", headers.join(" | "))?;
+    writeln!(outbuf, "|{}", " --- |".repeat(fields.len()))?;
+
+    for (id, item) in todo_items {
+        let cells = fields
+            .iter()
+            .map(|field| match *field {
+                "id" => markdown_title(id),
+                "title" => render.title(&item.title),
+                "category" => markdown_title(&item.category),
+                "path" => markdown_title(&display_path(&item.path)),
+                "line" => item.line.to_string(),
+                "column" => item.column.to_string(),
+                "done" if item.done => "done".to_string(),
+                "done" => "open".to_string(),
+                "raw" => markdown_title(&item.raw),
+                "labels" => markdown_title(&item.labels.join(", ")),
+                "kept" => item.kept.to_string(),
+                "depends_on" => markdown_title(&item.depends_on.join(", ")),
+                "author" => markdown_title(item.author.as_deref().unwrap_or_default()),
+                "notes" => markdown_title(&item.notes.join("; ")),
+                "cell" => item.cell.map(|cell| cell.to_string()).unwrap_or_default(),
+                "created_at" => item
+                    .created_at
+                    .map(|at| render.timestamp(at))
+                    .unwrap_or_default(),
+                "completed_at" => item
+                    .completed_at
+                    .map(|at| render.timestamp(at))
+                    .unwrap_or_default(),
+                "snoozed_until" => item
+                    .snoozed_until
+                    .map(|date| render.date(date))
+                    .unwrap_or_default(),
+                "location" if is_stdout => {
+                    markdown_title(&format!("{}:{}", display_path(&item.path), item.line))
+                }
+                "location" => markdown_title(&render.location(item, false)),
+                _ => unreachable!("fields are checked by parse_fields"),
+            })
+            .collect::<Vec<_>>();
+        writeln!(outbuf, "| {} |", cells.join(" | "))?;
+    }
+
+    Ok(())
+}
+
+/// The columns of `--format csv` without `--fields`.
+const CSV_COLUMNS: [&str; 6] = ["id", "category", "title", "path", "line", "done"];
+
+/// Renders `--format csv` with the given columns, or [`CSV_COLUMNS`] when
+/// there are none, see [`field_text`].
+fn write_todo_csv(
+    todo_items: &[(String, TodoItem)],
+    fields: &[&'static str],
+    outbuf: &mut impl Write,
+) -> Result<()> {
+    let fields = if fields.is_empty() {
+        &CSV_COLUMNS[..]
+    } else {
+        fields
+    };
+
+    let mut writer = csv::Writer::from_writer(outbuf);
+    writer.write_record(fields)?;
+    for (id, item) in todo_items {
+        writer.write_record(fields.iter().map(|field| field_text(id, item, field)))?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// The columns of `list --null` without `--fields`.
+const NULL_COLUMNS: [&str; 5] = ["id", "path", "line", "category", "title"];
+
+/// Renders `list --null` with the given fields, or [`NULL_COLUMNS`] when
+/// there are none: every field ends with a NUL, every record with another.
+fn write_todo_null(
+    todo_items: &[(String, TodoItem)],
+    fields: &[&'static str],
+    outbuf: &mut impl Write,
+) -> Result<()> {
+    let fields = if fields.is_empty() {
+        &NULL_COLUMNS[..]
+    } else {
+        fields
+    };
+
+    for (id, item) in todo_items {
+        for field in fields {
+            write!(outbuf, "{}\0", field_text(id, item, field))?;
+        }
+        write!(outbuf, "\0")?;
+    }
+
+    Ok(())
+}
+
+/// A field of [`TODO_FIELDS`] as plain text, for csv and `--null`. Lists
+/// are joined by `;`, notes by newlines.
+fn field_text(id: &str, item: &TodoItem, field: &str) -> String {
+    match field {
+        "id" => id.to_string(),
+        "title" => item.title.clone(),
+        "category" => item.category.clone(),
+        "path" => display_path(&item.path),
+        "line" => item.line.to_string(),
+        "column" => item.column.to_string(),
+        "done" => item.done.to_string(),
+        "raw" => item.raw.clone(),
+        "labels" => item.labels.join(";"),
+        "kept" => item.kept.to_string(),
+        "depends_on" => item.depends_on.join(";"),
+        "author" => item.author.clone().unwrap_or_default(),
+        "notes" => item.notes.join("\n"),
+        "cell" => item.cell.map(|cell| cell.to_string()).unwrap_or_default(),
+        "created_at" => item
+            .created_at
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default(),
+        "completed_at" => item
+            .completed_at
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default(),
+        "snoozed_until" => item
+            .snoozed_until
+            .map(|date| date.to_string())
+            .unwrap_or_default(),
+        _ => unreachable!("fields are checked by parse_fields"),
+    }
+}
+
+/// A JSON entry reduced to the selected fields, serialized in their order.
+struct SelectedFields<'a> {
+    entry: serde_json::Value,
+    fields: &'a [&'static str],
+}
+
+impl Serialize for SelectedFields<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for field in self.fields {
+            map.serialize_entry(field, &self.entry[field])?;
+        }
+        map.end()
+    }
+}
+
+fn write_todo_json_fields(
+    todo_items: &[(String, TodoItem)],
+    fields: &[&'static str],
+    outbuf: &mut impl Write,
+) -> Result<()> {
+    let entries = todo_items
+        .iter()
+        .map(|(id, item)| {
+            Ok(SelectedFields {
+                entry: serde_json::to_value(TodoEntry { id, item })?,
+                fields,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    serde_json::to_writer_pretty(&mut *outbuf, &entries).context("could not write JSON output")?;
+    writeln!(outbuf)?;
+
+    Ok(())
+}
+
+/// One directory or file of `--format tree`: directories hold children,
+/// files hold the TODOs found in them.
+#[derive(Default)]
+struct TreeNode<'a> {
+    children: BTreeMap<String, TreeNode<'a>>,
+    items: Vec<(&'a str, &'a TodoItem)>,
+}
+
+impl<'a> TreeNode<'a> {
+    fn build(todo_items: &'a [(String, TodoItem)]) -> Self {
+        let mut root = TreeNode::default();
+        for (id, item) in todo_items {
+            let path = normalize_path(&item.path);
+            let node = path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .fold(&mut root, |node, name| {
+                    node.children.entry(name).or_default()
+                });
+            node.items.push((id, item));
+        }
+
+        root
+    }
+
+    fn count(&self) -> usize {
+        self.items.len() + self.children.values().map(TreeNode::count).sum::<usize>()
+    }
+
+    fn write(&self, prefix: &str, boxed: bool, outbuf: &mut impl Write) -> Result<()> {
+        let entries = self.children.len() + self.items.len();
+        let branch = |i: usize| match (boxed, i + 1 == entries) {
+            (true, false) => ("├── ", "│   "),
+            (true, true) => ("└── ", "    "),
+            (false, _) => ("", "  "),
+        };
+
+        for (i, (name, child)) in self.children.iter().enumerate() {
+            let (branch, indent) = branch(i);
+            writeln!(outbuf, "{}{}{} ({})", prefix, branch, name, child.count())?;
+            child.write(&format!("{}{}", prefix, indent), boxed, outbuf)?;
+        }
+
+        for (i, (id, item)) in self.items.iter().enumerate() {
+            let (branch, _) = branch(self.children.len() + i);
+            writeln!(
+                outbuf,
+                "{}{}[{}] {}({}): {} (line {})",
+                prefix,
+                branch,
+                if item.done { "x" } else { " " },
+                item.category,
+                id,
+                item.title.trim(),
+                item.line
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders `--format tree`, using box-drawing characters only when the
+/// output is a terminal.
+fn write_todo_tree(
+    todo_items: &[(String, TodoItem)],
+    boxed: bool,
+    outbuf: &mut impl Write,
+) -> Result<()> {
+    let root = TreeNode::build(todo_items);
+    writeln!(outbuf, ". ({})", root.count())?;
+    root.write("", boxed, outbuf)?;
+
+    Ok(())
+}
+
+fn write_data_file(backend: DataBackend, root: &std::path::Path, todo: &TodoList) -> Result<()> {
+    match backend {
+        DataBackend::Json => write_json_data_file(root, todo),
+        DataBackend::Sqlite => sqlite_data::write(root, todo),
+    }
+}
+
+/// Writes `.mrdm/data.json` through a temp file, so an interrupted write
+/// never leaves it truncated.
+fn write_json_data_file(root: &std::path::Path, todo: &TodoList) -> Result<()> {
+    let path = root.join(OUT_PATH);
+    let tmp_path = path.with_extension("tmp");
+
+    let data_out = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)
+        .with_context(|| format!("could not open file `{}`", display_path(&path)))?;
+
+    let mut data_writer = BufWriter::new(data_out);
+    serde_json::to_writer_pretty(&mut data_writer, todo)
+        .with_context(|| format!("could not write to file `{}`", display_path(&path)))?;
+    data_writer
+        .flush()
+        .with_context(|| format!("could not write to file `{}`", display_path(&path)))?;
+
+    // overwrite the original file with the rewritten content
+    std::fs::rename(&tmp_path, &path).with_context(|| {
+        format!(
+            "could not rename file `{}` to `{}`",
+            display_path(&tmp_path),
+            display_path(&path)
+        )
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum HistoryEvent {
+    Created,
+    Done,
+    Undone,
+    Removed,
+    /// recreated under a new id, see `previous_id`
+    Reindexed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ItemState {
+    Open,
+    Done,
+}
+
+/// A line of `.mrdm/history.jsonl`. `from` and `to` are the states before
+/// and after, none where the item was not tracked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HistoryEntry {
+    at: chrono::DateTime<chrono::Utc>,
+    event: HistoryEvent,
+    id: String,
+    from: Option<ItemState>,
+    to: Option<ItemState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    previous_id: Option<String>,
+}
+
+impl HistoryEntry {
+    fn new(at: chrono::DateTime<chrono::Utc>, event: HistoryEvent, id: &str) -> Self {
+        let (from, to) = match event {
+            HistoryEvent::Created | HistoryEvent::Reindexed => (None, Some(ItemState::Open)),
+            HistoryEvent::Done => (Some(ItemState::Open), Some(ItemState::Done)),
+            HistoryEvent::Undone => (Some(ItemState::Done), Some(ItemState::Open)),
+            HistoryEvent::Removed => (Some(ItemState::Open), None),
+        };
+
+        Self {
+            at,
+            event,
+            id: id.to_string(),
+            from,
+            to,
+            previous_id: None,
+        }
+    }
+
+    /// One line of `todo log`.
+    fn render(&self, zone: DisplayZone) -> String {
+        let state = |state: Option<ItemState>| match state {
+            Some(ItemState::Open) => "open",
+            Some(ItemState::Done) => "done",
+            None => "-",
+        };
+        let event = serde_json::to_value(self.event).unwrap();
+
+        let mut line = format!(
+            "{}  {:<9} {} ({} → {})",
+            zone.format(self.at),
+            event.as_str().unwrap(),
+            self.id,
+            state(self.from),
+            state(self.to)
+        );
+        if let Some(previous) = &self.previous_id {
+            line.push_str(&format!(", was {}", previous));
+        }
+        line
+    }
+}
+
+fn append_history(
+    path: &std::path::Path,
+    entries: impl IntoIterator<Item = HistoryEntry>,
+) -> Result<()> {
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(&entry)?);
+        lines.push('\n');
+    }
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("could not create directory `{}`", display_path(dir)))?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .with_context(|| format!("could not write to file `{}`", display_path(path)))
+}
+
+/// The logged transitions, none when nothing was logged yet.
+fn read_history(path: &std::path::Path) -> Result<Vec<HistoryEntry>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(e).with_context(|| format!("could not read file `{}`", display_path(path)))
+        }
+    };
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!("could not parse line {} of `{}`", i + 1, display_path(path))
+            })
+        })
+        .collect()
+}
+
+/// `data_backend: sqlite`, one row per item. The item itself is stored as
+/// JSON so new fields need no migration, a few columns are kept alongside
+/// for querying the database directly.
+#[cfg(feature = "sqlite")]
+mod sqlite_data {
+    use super::{display_path, read_json_data_file, TodoList, DB_PATH};
+    use anyhow::{Context, Result};
+    use rusqlite::{params, Connection, OpenFlags};
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS items (
+        id TEXT PRIMARY KEY,
+        category TEXT NOT NULL,
+        title TEXT NOT NULL,
+        path TEXT NOT NULL,
+        done INTEGER NOT NULL,
+        item TEXT NOT NULL
+    )";
+
+    /// The stored JSON of every item by id.
+    fn stored_items(conn: &Connection) -> Result<HashMap<String, String>> {
+        let mut select = conn.prepare("SELECT id, item FROM items")?;
+        let rows = select.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub(super) fn read(root: &Path) -> Result<TodoList> {
+        let path = root.join(DB_PATH);
+        if !path.exists() {
+            return read_json_data_file(root);
+        }
+
+        let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("could not open file `{}`", display_path(&path)))?;
+        let items = stored_items(&conn)
+            .with_context(|| format!("could not read file `{}`", display_path(&path)))?
+            .into_iter()
+            .map(|(id, item)| Ok((id, serde_json::from_str(&item)?)))
+            .collect::<Result<_>>()
+            .with_context(|| format!("could not parse file `{}`", display_path(&path)))?;
+
+        Ok(TodoList::new(items))
+    }
+
+    /// Deletes the rows of items that are gone and upserts the ones that
+    /// changed, in a single transaction.
+    pub(super) fn write(root: &Path, todo: &TodoList) -> Result<()> {
+        let path = root.join(DB_PATH);
+        let mut conn = Connection::open(&path)
+            .with_context(|| format!("could not open file `{}`", display_path(&path)))?;
+        conn.execute_batch(SCHEMA)
+            .with_context(|| format!("could not write to file `{}`", display_path(&path)))?;
+
+        let tx = conn.transaction()?;
+        let stored = stored_items(&tx)?;
+
+        for id in stored.keys().filter(|id| !todo.items.contains_key(*id)) {
+            tx.execute("DELETE FROM items WHERE id = ?1", [id])?;
+        }
+
+        {
+            let mut upsert = tx.prepare(
+                "INSERT OR REPLACE INTO items (id, category, title, path, done, item)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (id, item) in &todo.items {
+                let json = serde_json::to_string(item)?;
+                if stored.get(id) == Some(&json) {
+                    continue;
+                }
+
+                upsert.execute(params![
+                    id,
+                    item.category,
+                    item.title,
+                    display_path(&item.path),
+                    item.done,
+                    json
+                ])?;
+            }
+        }
+
+        tx.commit()
+            .with_context(|| format!("could not write to file `{}`", display_path(&path)))
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+mod sqlite_data {
+    use super::{DataBackend, TodoList};
+    use anyhow::Result;
+    use std::path::Path;
+
+    pub(super) fn read(_root: &Path) -> Result<TodoList> {
+        Err(DataBackend::unsupported())
+    }
+
+    pub(super) fn write(_root: &Path, _todo: &TodoList) -> Result<()> {
+        Err(DataBackend::unsupported())
+    }
+}
+
+/// Renames the category of the comment of `item` to `marker`, e.g.
+/// `// TODO(3): x` to `// DONE(3): x`, keeping the `encoding` of the file.
+/// Returns whether the comment was found.
+fn mark_done_in_source(item: &TodoItem, id: &str, marker: &str, cfg: &CliConfig) -> Result<bool> {
+    let path = cfg.root.join(&item.path);
+    let encoding = cfg.source_encoding();
+    let Some(Ok((content, original))) = std::fs::read(&path)
+        .ok()
+        .and_then(|bytes| decode_source(bytes, encoding))
+    else {
+        return Ok(false);
+    };
+
+    let delimiters = cfg.delimiters();
+    let lines = content.lines().collect::<Vec<_>>();
+    let Some(index) = find_todo_line(&lines, id, item, &delimiters) else {
+        return Ok(false);
+    };
+
+    let (plain, prefixed) = (
+        format!(
+            "{}{}{}{}",
+            item.category, delimiters.open, id, delimiters.close
+        ),
+        format!("{}{}", item.category, delimiters.wrap(id)),
+    );
+
+    let file = ScannedFile {
+        path: item.path.clone(),
+        root: 0,
+        content,
+        scan_only: false,
+        encoded: original.map(|bytes| (encoding, bytes)),
+        matches: vec![],
+    };
+    let rewritten = rewritten_content(&file, |i, line| {
+        if i != index {
+            return None;
+        }
+
+        let needle = if line.contains(&prefixed) {
+            &prefixed
+        } else {
+            &plain
+        };
+        Some(line.replacen(
+            needle.as_str(),
+            &format!("{}{}", marker, &needle[item.category.len()..]),
+            1,
+        ))
+    })?;
+
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, rewritten)
+        .with_context(|| format!("could not write to temp file for `{}`", path.display()))?;
+    std::fs::rename(&tmp_path, &path).with_context(|| {
+        format!(
+            "could not rename file `{}` to `{}`",
+            tmp_path.display(),
+            path.display()
+        )
+    })?;
+
+    Ok(true)
+}
+
+/// The lines of the code `item` was found in, decoded from the `bytes` of
+/// its file as the scan does. For a notebook, the lines of its cell.
+fn source_lines_of(item: &TodoItem, bytes: Vec<u8>, cfg: &CliConfig) -> Result<Vec<String>> {
+    let (content, _) = decode_source(bytes, cfg.source_encoding()).context("it is binary")??;
+    let Some(cell) = item.cell else {
+        return Ok(content.lines().map(str::to_string).collect());
+    };
+
+    let extension = item.path.extension().unwrap_or_default();
+    let (_, extract) = EMBEDDED_CODE
+        .iter()
+        .find(|(ext, _)| extension == *ext)
+        .context("only notebooks have cells")?;
+    let embedded = extract(&content)?;
+
+    Ok(embedded
+        .code
+        .lines()
+        .zip(&embedded.origins)
+        .filter(|(_, (origin, _))| *origin == cell)
+        .map(|(line, _)| line.to_string())
+        .collect())
+}
+
+/// Finds the line of `item` in `lines`, starting at the recorded line and
+/// searching outwards for its id in case the code has moved since the scan.
+fn find_todo_line(
+    lines: &[&str],
+    id: &str,
+    item: &TodoItem,
+    delimiters: &IdDelimiters,
+) -> Option<usize> {
+    let recorded = item.line.saturating_sub(1);
+
+    (0..lines.len())
+        .flat_map(|distance| [recorded.checked_sub(distance), Some(recorded + distance)])
+        .flatten()
+        .filter(|i| *i < lines.len())
+        .find(|i| delimiters.mentioned_in(lines[*i], &item.category, id))
+}
+
+/// Everything `write_todo_items!` needs to know besides the items.
+#[derive(Debug, Clone, Default)]
+struct RenderOptions {
+    checkbox: CheckboxStyle,
+    /// omit the location suffix
+    flat: bool,
+    /// color stdout output
+    color: bool,
+    link_style: LinkStyle,
+    link_template: Option<String>,
+    /// fold done items in file output, see [`CliConfig::collapse_done`]
+    collapse_done: bool,
+    /// see [`CliConfig::max_title_width`]
+    max_title_width: Option<usize>,
+    /// show item dates this way, ISO timestamps in tables when unset
+    dates: Option<DateStyle>,
+}
+
+/// How `list --relative-dates` and `--absolute-dates` show a date.
+#[derive(Debug, Clone)]
+enum DateStyle {
+    /// relative to this moment
+    Relative(chrono::DateTime<chrono::Utc>),
+    Absolute(DisplayZone),
+}
+
+impl DateStyle {
+    fn format(&self, at: chrono::DateTime<chrono::Utc>) -> String {
+        match self {
+            Self::Relative(now) => relative_time(at, *now),
+            Self::Absolute(zone) => zone.format(at),
+        }
+    }
+}
+
+/// `3 days ago` or `in 2 weeks`, in the largest whole unit, months being
+/// 30 days and years 365.
+fn relative_time(at: chrono::DateTime<chrono::Utc>, now: chrono::DateTime<chrono::Utc>) -> String {
+    const UNITS: [(u64, &str); 6] = [
+        (365 * 24 * 60 * 60, "year"),
+        (30 * 24 * 60 * 60, "month"),
+        (7 * 24 * 60 * 60, "week"),
+        (24 * 60 * 60, "day"),
+        (60 * 60, "hour"),
+        (60, "minute"),
+    ];
+    let seconds = (now - at).num_seconds();
+    let Some((count, unit)) = UNITS
+        .iter()
+        .find(|(length, _)| seconds.unsigned_abs() >= *length)
+        .map(|(length, unit)| (seconds.unsigned_abs() / length, unit))
+    else {
+        return "just now".to_string();
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    if seconds < 0 {
+        format!("in {} {}{}", count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
+}
+
+impl RenderOptions {
+    fn new(cfg: &CliConfig) -> Self {
+        Self {
+            checkbox: cfg.checkbox_style.clone(),
+            flat: false,
+            color: false,
+            link_style: cfg.link_style,
+            link_template: cfg.link_template.clone(),
+            collapse_done: cfg.collapse_done,
+            max_title_width: cfg.max_title_width,
+            dates: None,
+        }
+    }
+
+    /// A timestamp as `--relative-dates` or `--absolute-dates` asked, else
+    /// in RFC 3339.
+    fn timestamp(&self, at: chrono::DateTime<chrono::Utc>) -> String {
+        match &self.dates {
+            Some(dates) => dates.format(at),
+            None => at.to_rfc3339(),
+        }
+    }
+
+    /// A snooze date, taken as its midnight in UTC when shown relative.
+    fn date(&self, date: chrono::NaiveDate) -> String {
+        match &self.dates {
+            Some(DateStyle::Relative(now)) => {
+                relative_time(date.and_time(chrono::NaiveTime::MIN).and_utc(), *now)
+            }
+            _ => date.to_string(),
+        }
+    }
+
+    /// ` created 3 days ago, done just now` after a markdown item, empty
+    /// without `--relative-dates` or `--absolute-dates`.
+    fn dates(&self, item: &TodoItem) -> String {
+        if self.dates.is_none() {
+            return String::new();
+        }
+        let dates = [
+            item.created_at
+                .map(|at| format!("created {}", self.timestamp(at))),
+            item.completed_at
+                .filter(|_| item.done)
+                .map(|at| format!("done {}", self.timestamp(at))),
+            item.snoozed_until
+                .map(|date| format!("snoozed until {}", self.date(date))),
+        ];
+        let dates = dates.into_iter().flatten().collect::<Vec<_>>();
+        if dates.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", dates.join(", "))
+        }
+    }
+
+    /// The title as shown in markdown, cut short at `max_title_width`
+    /// characters.
+    fn title(&self, title: &str) -> String {
+        markdown_title(&match self.max_title_width {
+            Some(width) if title.chars().count() > width => {
+                let mut title = title
+                    .chars()
+                    .take(width.saturating_sub(1))
+                    .collect::<String>();
+                title.push('…');
+                title
+            }
+            _ => title.to_string(),
+        })
+    }
+
+    /// `(path:line)` on stdout, a markdown link `[link](path#L12)` in files.
+    /// Notebook TODOs are at `(path:cell 3:2)` and link to the notebook.
+    fn location(&self, item: &TodoItem, is_stdout: bool) -> String {
+        if let Some(cell) = item.cell {
+            if is_stdout {
+                format!("({}:cell {}:{})", display_path(&item.path), cell, item.line)
+            } else {
+                format!("[link]({})", display_path(&item.path))
+            }
+        } else if is_stdout {
+            format!("({}:{})", display_path(&item.path), item.line)
+        } else {
+            format!(
+                "[link]({}{})",
+                display_path(&item.path),
+                self.link_style
+                    .fragment(self.link_template.as_deref(), item.line)
+            )
+        }
+    }
+}
+
+/// Keeps a title on one markdown line: embedded newlines collapse to a
+/// space and `|` is escaped so it can't split a table cell.
+fn markdown_title(title: &str) -> String {
+    title
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+macro_rules! write_todo_items {
+    ($todo_items:expr, $outbuf:expr, $is_stdout:expr, $render:expr) => {{
+        let render = &$render;
+        let color = $is_stdout && render.color;
+        let collapse = render.collapse_done && !$is_stdout;
+        let (open, done): (Vec<_>, Vec<_>) = $todo_items
+            .into_iter()
+            .partition(|(_, item)| !(collapse && item.done));
+
+        for (folded, items) in [(false, open), (true, done)] {
+            let folded = folded && !items.is_empty();
+            if folded {
+                writeln!(
+                    $outbuf,
+                    "\n<details>\n<summary>Done ({})</summary>\n",
+                    items.len()
+                )?;
+            }
+
+            for (id, item) in items {
+                let entry = format!(
+                    "{}: {}",
+                    paint(&format!("{}({})", item.category, id), YELLOW, color),
+                    render.title(&item.title),
+                );
+                write!(
+                    $outbuf,
+                    "{} [{}] {}",
+                    render.checkbox.bullet,
+                    if item.done {
+                        paint(&render.checkbox.done, GREEN, color)
+                    } else {
+                        " ".to_string()
+                    },
+                    if folded {
+                        format!("~~{}~~", entry)
+                    } else {
+                        entry
+                    },
+                )?;
+                if !render.flat {
+                    let location = render.location(&item, $is_stdout);
+                    write!($outbuf, " {}", paint(&location, DIM, color))?;
+                }
+                if let Some(author) = &item.author {
+                    write!($outbuf, " by {}", author)?;
+                }
+                write!($outbuf, "{}", render.dates(&item))?;
+                writeln!($outbuf)?;
+            }
+
+            if folded {
+                writeln!($outbuf, "\n</details>")?;
+            }
+        }
+    }};
+}
+
+/// `MRDM_PAGER`, else `PAGER`, else `less`, fed through its stdin. Dropping
+/// it waits for the pager to exit, so the shell prompt does not come back
+/// underneath it.
+struct Pager {
+    child: std::process::Child,
+    stdin: Option<std::process::ChildStdin>,
+}
+
+impl Pager {
+    /// `None` when the pager is `cat` or could not be started.
+    fn spawn() -> Option<Self> {
+        let command = std::env::var("MRDM_PAGER")
+            .or_else(|_| std::env::var("PAGER"))
+            .unwrap_or_else(|_| "less".to_string());
+        let mut words = command.split_whitespace();
+        let program = words.next().filter(|program| *program != "cat")?;
+
+        let mut pager = std::process::Command::new(program);
+        pager.args(words).stdin(std::process::Stdio::piped());
+        // like git: quit when it fits on one screen, keep colors
+        if std::env::var_os("LESS").is_none() {
+            pager.env("LESS", "FRX");
+        }
+
+        match pager.spawn() {
+            Ok(mut child) => {
+                let stdin = child.stdin.take();
+                Some(Self { child, stdin })
+            }
+            Err(e) => {
+                debug!("could not start pager `{}`: {}", program, e);
+                None
+            }
+        }
+    }
+
+    /// Quitting the pager before the end closes the pipe, which is fine.
+    fn ignore_quit(result: std::io::Result<()>) -> std::io::Result<()> {
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let stdin = self.stdin.as_mut().unwrap();
+        Self::ignore_quit(stdin.write_all(buf)).map(|_| buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Self::ignore_quit(self.stdin.as_mut().unwrap().flush())
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        self.child.wait().ok();
+    }
+}
+
+/// `page` pipes stdout through [`Pager`], it is ignored for `--out` files.
+fn get_outbuf(
+    out: Option<std::path::PathBuf>,
+    cfg: &CliConfig,
+    create_dirs: bool,
+    page: bool,
+) -> Result<(BufWriter<Box<dyn Write>>, bool)> {
+    let out = out
+        .or_else(|| cfg.out.clone())
+        .map(|out| cfg.root.join(out));
+
+    match out {
+        Some(ref path) => {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                if create_dirs {
+                    std::fs::create_dir_all(parent).with_context(|| {
+                        format!("could not create directory `{}`", display_path(parent))
+                    })?;
+                } else if !parent.is_dir() {
+                    anyhow::bail!(
+                        "directory `{}/` does not exist; use --create-dirs",
+                        display_path(parent)
+                    );
+                }
+            }
+
+            let file = std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+                .with_context(|| format!("could not open file `{}`", &path.display()))?;
+
+            Ok((BufWriter::new(Box::new(file)), false))
+        }
+        None => match page.then(Pager::spawn).flatten() {
+            Some(pager) => Ok((BufWriter::new(Box::new(pager)), true)),
+            None => Ok((BufWriter::new(Box::new(std::io::stdout())), true)),
+        },
+    }
+}
+
+/// Runs the parsed command line, reporting a failure on stderr in the
+/// requested `--error-format`.
+pub fn run_cli(args: Cli) -> std::process::ExitCode {
+    let error_format = args.error_format;
+
+    match run(args) {
+        Ok(code) => code,
+        Err(err) => {
+            error_format.report(&err);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Cli) -> Result<std::process::ExitCode> {
+    let mut cfg = get_config_at(
+        args.root.as_deref(),
+        args.config.as_deref(),
+        args.profile.as_deref(),
+    )?;
+    cfg.checkbox_style.validate()?;
+    IdDelimiters::parse(&cfg.id_delimiters)?;
+    cfg.link_style.validate(cfg.link_template.as_deref())?;
+    cfg.data_backend.validate()?;
+    cfg.badge.validate()?;
+    let zone = DisplayZone::parse(cfg.timezone.as_deref())?;
+    parse_encoding(cfg.encoding.as_deref())?;
+    if let Some(list) = args.paths_from.clone() {
+        cfg.include_from = Some(list);
+    }
+    cfg.ignore_case |= args.ignore_case;
+    if let Some(width) = args.max_title_width {
+        cfg.max_title_width = Some(width);
+    }
+    let color = args.color.enabled(std::io::stdout().is_terminal());
+    let page = !args.no_pager && (args.pager || std::io::stdout().is_terminal());
+    let confirm_each = args.confirm_each && std::io::stderr().is_terminal() && open_tty().is_some();
+    let jobs = if args.no_parallel {
+        Some(1)
+    } else {
+        args.jobs.map(std::num::NonZeroUsize::get)
+    };
+    let render = RenderOptions {
+        color,
+        ..RenderOptions::new(&cfg)
+    };
+
+    if args.print_config {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &cfg).context("could not write the config")?;
+        writeln!(stdout)?;
+        return Ok(std::process::ExitCode::SUCCESS);
+    }
+    let Some(command) = args.command else {
+        use clap::CommandFactory;
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingSubcommand,
+                "a subcommand is required unless --print-config is given",
+            )
+            .exit();
+    };
+
+    // `try_init`, so the tests can run more than one command per process
+    let _ =
+        env_logger::Builder::from_env(env_logger::Env::new().default_filter_or("debug")).try_init();
+
+    match command {
+        Commands::Init {
+            preset,
+            list_presets,
+        } => {
+            if list_presets {
+                for preset in <Preset as clap::ValueEnum>::value_variants() {
+                    let value = clap::ValueEnum::to_possible_value(preset).unwrap();
+                    println!("{}: {}", value.get_name(), value.get_help().unwrap());
+                }
+                return Ok(std::process::ExitCode::SUCCESS);
+            }
+
+            // make a mrdm.json file in the root
+            let config_path = std::env::current_dir()?.join(&cfg.root).join(CONFIG_PATH);
+
+            if config_path.exists() {
+                // if file exists, then error as it should not be overwritten
+                return Err(anyhow::anyhow!(
+                    "config file `{}` already exists",
+                    &config_path.display()
+                ));
+            }
+
+            // write default config copied from ./config/mrdm.json, or the
+            // preset from ./config/presets
+            let default_config = match preset {
+                Some(preset) => preset.config(),
+                None => include_str!("./config/mrdm.json"),
+            };
+
+            std::fs::write(&config_path, default_config)
+                .with_context(|| format!("could not write file `{}`", &config_path.display()))?;
+        }
+        Commands::Todo(todo_args) => {
+            let todo_cmd = todo_args.command;
+
+            match todo_cmd {
+                TodoCommands::List {
+                    out,
+                    pattern,
+                    path,
+                    max_results,
+                    offset,
+                    format,
+                    new_only,
+                    only_tracked,
+                    archive,
+                    filter,
+                    flat,
+                    no_rewrite,
+                    stats,
+                    fields,
+                    dedupe,
+                    blocked,
+                    blame,
+                    changed_since,
+                    show_snoozed,
+                    only_new,
+                    authors,
+                    exit_on_empty,
+                    hide_done,
+                    show_done,
+                    fail_if_untracked,
+                    null,
+                    output_on_change_only,
+                    relative_dates,
+                    absolute_dates,
+                    dry_run,
+                    show_diff,
+                    sort,
+                } => {
+                    let dates = if relative_dates {
+                        Some(DateStyle::Relative(chrono::Utc::now()))
+                    } else if absolute_dates {
+                        Some(DateStyle::Absolute(zone))
+                    } else {
+                        None
+                    };
+                    let render = RenderOptions {
+                        flat,
+                        dates,
+                        ..render
+                    };
+                    // --null picks from the same fields as csv
+                    let fields =
+                        parse_fields(&fields, if null { OutputFormat::Csv } else { format })?;
+                    let mut minted = vec![];
+
+                    let mut todo_items = if only_tracked {
+                        sort_todo_items(read_data_file(cfg.data_backend, &cfg.root)?.items)
+                    } else if let Some(archive) = &archive {
+                        let mut files = scan_archive(&cfg.root.join(archive), &pattern, &cfg)?;
+                        assign_placeholder_ids(&mut files);
+
+                        sort_todo_items(
+                            files
+                                .into_iter()
+                                .flat_map(|file| file.matches)
+                                .map(|m| (m.id.unwrap(), m.item)),
+                        )
+                    } else {
+                        let _lock = DataLock::acquire(&cfg.root)?;
+                        let prev_todo = read_data_file(cfg.data_backend, &cfg.root)?;
+
+                        let options = ScanOptions {
+                            next_id: next_id(&prev_todo.items),
+                            no_rewrite: no_rewrite || fail_if_untracked || !cfg.auto_assign_ids,
+                            tracked: prev_todo.items.clone(),
+                            strict: args.strict,
+                            force: args.force,
+                            confirm_each,
+                            dry_run,
+                            jobs,
+                            ..Default::default()
+                        };
+
+                        let started = std::time::Instant::now();
+                        let mut todo_items = get_todos(&pattern, path, &cfg, &options)?;
+                        if show_diff {
+                            let mut previews = options.stats.previews.lock().unwrap().clone();
+                            previews.sort();
+                            let (mut outbuf, _) = get_outbuf(out, &cfg, args.create_dirs, page)?;
+                            for preview in previews {
+                                write!(outbuf, "{}", preview)?;
+                            }
+                            outbuf.flush().context("could not write the output")?;
+                            return Ok(std::process::ExitCode::SUCCESS);
+                        }
+                        carry_over(&mut todo_items, &prev_todo.items);
+
+                        if output_on_change_only
+                            && diff_todos(&prev_todo.items, &todo_items) == TodoDiff::default()
+                        {
+                            return Ok(std::process::ExitCode::from(UNCHANGED_EXIT_CODE));
+                        }
+
+                        if stats {
+                            eprintln!("{}", options.stats.report(started.elapsed()));
+                        }
+                        minted = options.stats.minted.lock().unwrap().clone();
+
+                        let todo_items = sort_todo_items(todo_items);
+                        if fail_if_untracked {
+                            let untracked = todo_items
+                                .iter()
+                                .filter(|(id, _)| id.starts_with(PLACEHOLDER_ID_PREFIX))
+                                .collect::<Vec<_>>();
+                            for (_, item) in &untracked {
+                                eprintln!(
+                                    "{}:{}: {} without an id: {}",
+                                    display_path(&item.path),
+                                    item.line,
+                                    item.category,
+                                    item.title
+                                );
+                            }
+                            if !untracked.is_empty() {
+                                return Err(anyhow::anyhow!(
+                                    "{} TODOs have no id, run `mrdm todo done` to assign them",
+                                    untracked.len()
+                                ));
+                            }
+                        }
+                        todo_items
+                    };
+
+                    if only_new {
+                        todo_items.retain(|(id, _)| minted.contains(id));
+                        let summary = DoneSummary {
+                            new: minted,
+                            ..Default::default()
+                        };
+                        eprintln!("{}", summary.render());
+                    }
+
+                    if blocked {
+                        let open = todo_items
+                            .iter()
+                            .filter(|(_, item)| !item.done)
+                            .map(|(id, _)| id.clone())
+                            .collect::<HashSet<_>>();
+                        todo_items.retain(|(_, item)| is_blocked(item, &open));
+                    }
+                    todo_items.retain(|(_, item)| filter.matches(item));
+                    // an explicit `--status done` asks for them anyway
+                    if (hide_done || cfg.hide_done && !show_done)
+                        && filter.status != StatusFilter::Done
+                    {
+                        todo_items.retain(|(_, item)| !item.done);
+                    }
+                    if !show_snoozed {
+                        let today = chrono::Local::now().date_naive();
+                        todo_items.retain(|(_, item)| !is_snoozed(item, today));
+                    }
+                    if let Some(window) = changed_since {
+                        let since = chrono::Utc::now() - window;
+                        todo_items.retain(|(_, item)| is_changed_since(item, since));
+                    }
+                    if dedupe {
+                        todo_items = dedupe_todo_items(todo_items, cfg.ignore_case);
+                    }
+
+                    if new_only {
+                        match changed_since_base(&cfg) {
+                            Some(changed) => todo_items.retain(|(_, item)| {
+                                changed
+                                    .iter()
+                                    .any(|path| path.as_path() == normalize_path(&item.path))
+                            }),
+                            None => warn!(
+                                "--new-only needs a git repository with a base branch, listing everything"
+                            ),
+                        }
+                    }
+
+                    sort.sort(&mut todo_items, &cfg.patterns, args.reverse);
+
+                    let exit_code = if todo_items.is_empty() {
+                        std::process::ExitCode::from(exit_on_empty)
+                    } else {
+                        std::process::ExitCode::SUCCESS
+                    };
+
+                    if authors {
+                        todo_items.retain(|(_, item)| !item.done);
+                        blame_todo_items(&mut todo_items, &cfg.root);
+
+                        let counts = count_authors(&todo_items);
+                        let (mut outbuf, _) = get_outbuf(out, &cfg, args.create_dirs, page)?;
+                        write_author_counts(&counts, format, &mut outbuf)?;
+                        outbuf.flush().context("could not write the output")?;
+                        return Ok(exit_code);
+                    }
+
+                    let total = todo_items.len();
+                    let mut todo_items = todo_items
+                        .into_iter()
+                        .skip(offset)
+                        .take(max_results.unwrap_or(usize::MAX))
+                        .collect::<Vec<_>>();
+
+                    if blame {
+                        blame_todo_items(&mut todo_items, &cfg.root);
+                    }
+
+                    if todo_items.len() < total {
+                        eprintln!(
+                            "showing {} of {} TODOs (offset {})",
+                            todo_items.len(),
+                            total,
+                            offset
+                        );
+                    }
+
+                    let (mut outbuf, is_stdout) = get_outbuf(out, &cfg, args.create_dirs, page)?;
+                    match format {
+                        _ if null => write_todo_null(&todo_items, &fields, &mut outbuf)?,
+                        OutputFormat::Markdown => {
+                            write_todo_items!(todo_items, outbuf, is_stdout, render)
+                        }
+                        OutputFormat::Json if fields.is_empty() => {
+                            write_todo_json(&todo_items, &mut outbuf)?
+                        }
+                        OutputFormat::Json => {
+                            write_todo_json_fields(&todo_items, &fields, &mut outbuf)?
+                        }
+                        OutputFormat::MarkdownTable => {
+                            write_todo_table(&todo_items, &fields, &render, is_stdout, &mut outbuf)?
+                        }
+                        OutputFormat::Csv => write_todo_csv(&todo_items, &fields, &mut outbuf)?,
+                        OutputFormat::Sarif => write_todo_sarif(&todo_items, &mut outbuf)?,
+                        OutputFormat::Tree => write_todo_tree(
+                            &todo_items,
+                            is_stdout && std::io::stdout().is_terminal(),
+                            &mut outbuf,
+                        )?,
+                    }
+                    outbuf.flush().context("could not write the output")?;
+                    return Ok(exit_code);
+                }
+                TodoCommands::Done {
+                    pattern,
+                    path,
+                    out,
+                    reconcile,
+                    summary_only,
+                    staged,
+                    ids,
+                } => {
+                    let staged = if staged {
+                        let files = staged_files(&cfg.root).ok_or_else(|| {
+                            anyhow::anyhow!("--staged needs to run inside a git repository")
+                        })?;
+                        Some(files.into_iter().collect::<HashSet<_>>())
+                    } else {
+                        None
+                    };
+
+                    // if .mrdm directory does not exist, create it
+                    std::fs::create_dir(cfg.root.join(".mrdm")).ok();
+                    let _lock = DataLock::acquire(&cfg.root)?;
+
+                    let prev_todo = read_data_file(cfg.data_backend, &cfg.root)?;
+                    let tracked = prev_todo.items.keys().cloned().collect::<HashSet<_>>();
+                    let now = chrono::Utc::now();
+
+                    if !ids.is_empty() {
+                        let mut todo = prev_todo;
+                        let mut summary = DoneSummary::default();
+
+                        if let Some(unknown) = ids.iter().find(|id| !todo.items.contains_key(*id)) {
+                            return Err(anyhow::anyhow!("unknown id `{}`", unknown));
+                        }
+
+                        for id in &ids {
+                            let item = todo.items.get_mut(id).unwrap();
+                            if item.done {
+                                continue;
+                            }
+
+                            // the config loader lowercases map keys
+                            let marker = cfg
+                                .done_markers
+                                .iter()
+                                .find(|(category, _)| category.eq_ignore_ascii_case(&item.category))
+                                .map(|(_, marker)| marker);
+
+                            let renamed = match marker {
+                                Some(marker) => {
+                                    let renamed = mark_done_in_source(item, id, marker, &cfg)?;
+                                    if !renamed {
+                                        warn!(
+                                            "{}({}) was not found in `{}`, its comment is unchanged",
+                                            item.category,
+                                            id,
+                                            display_path(&item.path)
+                                        );
+                                    }
+                                    renamed
+                                }
+                                None => false,
+                            };
+
+                            // a comment left in the source must not reopen the item
+                            item.done = true;
+                            item.kept = !renamed;
+                            summary.marked_done.push(id.clone());
+                        }
+
+                        stamp_transitions(&mut todo.items, &tracked, now);
+                        write_data_file(cfg.data_backend, &cfg.root, &todo)?;
+                        if cfg.history {
+                            append_history(&cfg.root.join(HISTORY_PATH), summary.history(now))?;
+                        }
+
+                        if summary_only {
+                            println!("{}", summary.render());
+                        } else {
+                            let (mut outbuf, is_stdout) =
+                                get_outbuf(out, &cfg, args.create_dirs, false)?;
+                            write_todo_items!(
+                                sort_todo_items(todo.items),
+                                outbuf,
+                                is_stdout,
+                                render
+                            );
+                            outbuf.flush().context("could not write the output")?;
+                            eprintln!("{}", summary.render());
+                        }
+
+                        run_hooks(
+                            &[("post_done", &cfg.hooks.post_done)],
+                            cfg.data_backend,
+                            &cfg.root,
+                        );
+                        return Ok(std::process::ExitCode::SUCCESS);
+                    }
+
+                    let options = ScanOptions {
+                        next_id: next_id(&prev_todo.items),
+                        tracked: prev_todo.items.clone(),
+                        strict: args.strict,
+                        only: staged.clone(),
+                        confirm_each,
+                        jobs,
+                        ..Default::default()
+                    };
+                    let mut curr_todo = get_todos(&pattern, path, &cfg, &options)?;
+                    // declined by --confirm-each, so not tracked yet
+                    curr_todo.retain(|id, _| !id.starts_with(PLACEHOLDER_ID_PREFIX));
+                    carry_over(&mut curr_todo, &prev_todo.items);
+
+                    // --summary-only must not truncate the `out` file
+                    let is_stdout = out.is_none() && cfg.out.is_none();
+                    let mut outbuf = if summary_only {
+                        None
+                    } else {
+                        Some(get_outbuf(out, &cfg, args.create_dirs, false)?.0)
+                    };
+
+                    let mut diff = diff_todos(&prev_todo.items, &curr_todo);
+
+                    // items of files that were not scanned are not gone
+                    if let Some(staged) = &staged {
+                        diff.deleted.retain(|key| {
+                            staged.contains(normalize_path(&prev_todo.items[key].path))
+                        });
+                    }
+
+                    let mut summary = DoneSummary {
+                        new: diff.added.clone(),
+                        ..Default::default()
+                    };
+
+                    let mut final_todo = prev_todo
+                        .items
+                        .into_iter()
+                        .chain(curr_todo)
+                        .collect::<HashMap<_, _>>();
+
+                    let stdout = std::io::stdout();
+
+                    let mut handle = stdout.lock();
+
+                    // set status of done items to true
+                    for key in &diff.deleted {
+                        if let Some(item) = final_todo.get_mut(key.as_str()) {
+                            if reconcile {
+                                info!(
+                                    "reconcile: {}({}) is no longer in the code, marking it as done",
+                                    item.category, key
+                                );
+                                item.done = true;
+                                summary.marked_done.push(key.clone());
+                                continue;
+                            }
+
+                            // prompt user to confirm deletion
+                            let prompt = format!(
+                                "{}\n\
+                                - [ ] {}: {} {}\n\
+                                Do you want to mark it as done or remove it from the list? (d/r)",
+                                paint(
+                                    "This todo item was removed from your codebase:",
+                                    BOLD,
+                                    color
+                                ),
+                                item.category,
+                                item.title.trim(),
+                                render.location(item, is_stdout),
+                            );
+
+                            writeln!(handle, "{}", prompt)?;
+
+                            handle.flush()?;
+
+                            // without a terminal, do what --reconcile does
+                            let input = read_tty_line()?.unwrap_or_else(|| "d".to_string());
+
+                            if input.trim().to_lowercase() == "d" {
+                                item.done = true;
+                                summary.marked_done.push(key.clone());
+                            } else {
+                                final_todo.remove(key.as_str());
+                                summary.removed.push(key.clone());
+                            }
+                        }
+                    }
+
+                    // items that were done but are now undone
+                    for key in &diff.undone {
+                        if let Some(item) = final_todo.get_mut(key.as_str()) {
+                            if reconcile {
+                                info!(
+                                    "reconcile: {}({}) is back in the code as `{}`, reopening it",
+                                    item.category,
+                                    key,
+                                    item.title.trim()
+                                );
+                                item.done = false;
+                                summary.reopened.push(key.clone());
+                                continue;
+                            }
+
+                            // prompt user to confirm deletion
+                            let prompt = format!(
+                                "{}\n\
+                                - [x] {}: {} {}\n\
+                                Do you want to mark it as undone or recreate it? (u/r)",
+                                paint(
+                                    "This todo item was marked as done but is now undone:",
+                                    BOLD,
+                                    color
+                                ),
+                                item.category,
+                                item.title.trim(),
+                                render.location(item, is_stdout),
+                            );
+
+                            writeln!(handle, "{}", prompt)?;
+
+                            handle.flush()?;
+
+                            let input = read_tty_line()?.unwrap_or_else(|| "u".to_string());
+
+                            if input.trim().to_lowercase() == "u" {
+                                item.done = false;
+                                summary.reopened.push(key.clone());
+                            } else {
+                                let id = recreate_item(&mut final_todo, key);
+                                summary.recreated.push((key.clone(), id));
+                            }
+                        }
+                    }
+
+                    stamp_transitions(&mut final_todo, &tracked, now);
+                    let final_todo = sort_todo_items(final_todo);
+
+                    if let Some(outbuf) = &mut outbuf {
+                        write_todo_items!(&final_todo, outbuf, is_stdout, render);
+                    }
+
+                    write_data_file(
+                        cfg.data_backend,
+                        &cfg.root,
+                        &TodoList::new(final_todo.into_iter().collect()),
+                    )?;
+                    if cfg.history {
+                        append_history(&cfg.root.join(HISTORY_PATH), summary.history(now))?;
+                    }
+
+                    if staged.is_some() {
+                        let rewritten = options.stats.rewritten.lock().unwrap().clone();
+                        if !rewritten.is_empty() {
+                            let mut git_args = vec!["add", "--"];
+                            git_args.extend(rewritten.iter().filter_map(|path| path.to_str()));
+                            git_output(&cfg.root, &git_args).ok_or_else(|| {
+                                anyhow::anyhow!("could not stage the files with injected ids")
+                            })?;
+                        }
+                    }
+
+                    if let Some(outbuf) = &mut outbuf {
+                        outbuf.flush().context("could not write the output")?;
+                    }
+                    if summary_only {
+                        println!("{}", summary.render());
+                    } else {
+                        eprintln!("{}", summary.render());
+                    }
+
+                    let mut hooks = vec![("post_scan", &cfg.hooks.post_scan)];
+                    if !summary.marked_done.is_empty() {
+                        hooks.push(("post_done", &cfg.hooks.post_done));
+                    }
+                    run_hooks(&hooks, cfg.data_backend, &cfg.root);
+                }
+                TodoCommands::Diff {
+                    against,
+                    pattern,
+                    path,
+                    out,
+                } => {
+                    let _lock = DataLock::acquire(&cfg.root)?;
+                    let against = cfg.root.join(against);
+                    let against_in = std::fs::File::open(&against)
+                        .with_context(|| format!("could not open file `{}`", against.display()))?;
+                    let mut old_todo: TodoList =
+                        serde_json::from_reader(BufReader::new(against_in)).with_context(|| {
+                            format!("could not parse file `{}`", against.display())
+                        })?;
+                    old_todo.migrate()?;
+
+                    let data_todo = read_data_file(cfg.data_backend, &cfg.root)
+                        .unwrap_or_else(|_| TodoList::new(HashMap::new()));
+
+                    let options = ScanOptions {
+                        next_id: next_id(&data_todo.items),
+                        no_rewrite: !cfg.auto_assign_ids,
+                        tracked: data_todo.items.clone(),
+                        strict: args.strict,
+                        force: args.force,
+                        confirm_each,
+                        jobs,
+                        ..Default::default()
+                    };
+                    let mut curr_todo = get_todos(&pattern, path, &cfg, &options)?;
+                    carry_over(&mut curr_todo, &data_todo.items);
+
+                    // the scan only knows about open items, completed ones live in the data file
+                    let mut new_todo = data_todo
+                        .items
+                        .into_iter()
+                        .filter(|(_, item)| item.done)
+                        .collect::<HashMap<_, _>>();
+                    new_todo.extend(curr_todo);
+
+                    let diff = diff_todos(&old_todo.items, &new_todo);
+
+                    let (mut outbuf, is_stdout) = get_outbuf(out, &cfg, args.create_dirs, page)?;
+
+                    for (heading, ids, items) in [
+                        ("Added", &diff.added, &new_todo),
+                        ("Removed", &diff.deleted, &old_todo.items),
+                        ("Completed", &diff.completed, &new_todo),
+                    ] {
+                        writeln!(outbuf, "### {} ({})\n", heading, ids.len())?;
+
+                        let section = ids
+                            .iter()
+                            .filter_map(|id| items.get(id).map(|item| (id, item)))
+                            .collect::<Vec<_>>();
+                        if section.is_empty() {
+                            writeln!(outbuf, "_None_")?;
+                        }
+                        write_todo_items!(section, outbuf, is_stdout, render);
+
+                        writeln!(outbuf)?;
+                    }
+                    outbuf.flush().context("could not write the output")?;
+                }
+                TodoCommands::Check { pattern, path, fix } => {
+                    let _lock = DataLock::acquire(&cfg.root)?;
+                    let data_todo = read_data_file(cfg.data_backend, &cfg.root)?;
+
+                    let options = ScanOptions {
+                        jobs,
+                        ..Default::default()
+                    };
+                    let (re, mut files) = scan_files(&pattern, path, &cfg, &options)?;
+                    let lost = match cfg.tracking {
+                        Tracking::Id => find_lost_ids(&files, &data_todo.items),
+                        Tracking::Hash => vec![],
+                    };
+                    let disallowed = disallowed_categories(&files, &cfg)?;
+
+                    let scanned = files
+                        .iter()
+                        .flat_map(|file| &file.matches)
+                        .filter_map(|m| Some((m.id.as_deref()?, &m.item)))
+                        .collect::<Vec<_>>();
+                    for problem in dependency_problems(&scanned, &data_todo.items) {
+                        warn!("{}", problem);
+                    }
+
+                    for lost in &lost {
+                        println!("{}", lost.describe(&files));
+                    }
+                    for (index, line, category) in &disallowed {
+                        println!(
+                            "{}:{}: category `{}` is not in allowed_categories",
+                            display_path(&files[*index].path),
+                            line,
+                            category
+                        );
+                    }
+
+                    if lost.is_empty() {
+                        eprintln!("no tracked TODO lost its id");
+                    } else if fix {
+                        restore_lost_ids(&mut files, &lost, true);
+                        for file in &files {
+                            write_back(file, &cfg.root, &re, &cfg.delimiters())?;
+                        }
+                        eprintln!("restored {} ids", lost.len());
+                    } else {
+                        return Err(anyhow::anyhow!(
+                            "{} tracked TODOs lost their id, run with --fix to restore them",
+                            lost.len()
+                        ));
+                    }
+
+                    if !disallowed.is_empty() {
+                        return Err(anyhow::anyhow!(
+                            "{} TODOs use a category outside allowed_categories",
+                            disallowed.len()
+                        ));
+                    }
+                }
+                TodoCommands::StripIds {
+                    pattern,
+                    path,
+                    yes: _,
+                    dry_run,
+                    delete_data,
+                } => {
+                    let _lock = DataLock::acquire(&cfg.root)?;
+
+                    let options = ScanOptions {
+                        jobs,
+                        ..Default::default()
+                    };
+                    let (re, files) = scan_files(&pattern, path, &cfg, &options)?;
+                    let (mut stripped, mut changed_files) = (0, 0);
+
+                    for file in &files {
+                        let lines = strip_id_lines(file, &re);
+                        if lines.is_empty() {
+                            continue;
+                        }
+
+                        stripped += lines.len();
+                        changed_files += 1;
+
+                        if dry_run {
+                            let mut lines = lines.into_iter().collect::<Vec<_>>();
+                            lines.sort();
+                            for (i, line) in lines {
+                                println!("{}:{}: {}", display_path(&file.path), i + 1, line.trim());
+                            }
+                        } else {
+                            if !rewrite_lines(file, &cfg.root, |i, _| lines.get(&i).cloned())? {
+                                stripped -= lines.len();
+                                changed_files -= 1;
+                            }
+                        }
+                    }
+
+                    if dry_run {
+                        eprintln!("would strip {} ids from {} files", stripped, changed_files);
+                        return Ok(std::process::ExitCode::SUCCESS);
+                    }
+                    eprintln!("stripped {} ids from {} files", stripped, changed_files);
+
+                    if delete_data {
+                        for data_path in [OUT_PATH, DB_PATH] {
+                            let data_path = cfg.root.join(data_path);
+                            match std::fs::remove_file(&data_path) {
+                                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                                    return Err(e).with_context(|| {
+                                        format!(
+                                            "could not delete file `{}`",
+                                            display_path(&data_path)
+                                        )
+                                    });
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+                TodoCommands::Note { id, text } => {
+                    let _lock = DataLock::acquire(&cfg.root)?;
+                    let mut todo_list = read_data_file(cfg.data_backend, &cfg.root)?;
+                    let item = todo_list
+                        .items
+                        .get_mut(&id)
+                        .with_context(|| format!("no TODO with id `{}`", id))?;
+                    item.notes.push(text.trim().to_string());
+                    info!("{} has {} notes", id, item.notes.len());
+
+                    write_data_file(cfg.data_backend, &cfg.root, &todo_list)?;
+                }
+                TodoCommands::Log { id } => {
+                    let stdout = std::io::stdout();
+                    let mut handle = stdout.lock();
+                    for entry in read_history(&cfg.root.join(HISTORY_PATH))? {
+                        if id.as_ref().is_none_or(|id| *id == entry.id) {
+                            writeln!(handle, "{}", entry.render(zone))?;
+                        }
+                    }
+                }
+                TodoCommands::Badge { out } => {
+                    let todo_list = read_data_file(cfg.data_backend, &cfg.root)?;
+                    let open = todo_list.items.values().filter(|item| !item.done).count();
+                    let svg = cfg.badge.render(open);
+
+                    match out.map(|path| cfg.root.join(path)) {
+                        Some(path) => std::fs::write(&path, svg).with_context(|| {
+                            format!("could not write file `{}`", display_path(&path))
+                        })?,
+                        None => print!("{}", svg),
+                    }
+                }
+                TodoCommands::Regex { pattern } => {
+                    let patterns = resolve_pattern(&pattern, &cfg);
+                    let delimiters = cfg.delimiters();
+                    let re = create_regex(
+                        patterns.iter().map(String::as_str).collect(),
+                        &cfg.comment_markers,
+                        &delimiters,
+                        cfg.ignore_case,
+                    )?;
+
+                    println!("patterns: {}", patterns.join(", "));
+                    println!("comment markers: {}", cfg.comment_markers.join(", "));
+                    println!("minted ids: {}", delimiters.wrap("3"));
+                    println!("regex: {}", re.as_str());
+                }
+                TodoCommands::Grep {
+                    query,
+                    regex,
+                    scan,
+                    filter,
+                    format,
+                    out,
+                } => {
+                    let matcher = title_matcher(&query, regex)?;
+
+                    let mut todo_items = if scan {
+                        let _lock = DataLock::acquire(&cfg.root)?;
+                        let data_todo = read_data_file(cfg.data_backend, &cfg.root)
+                            .unwrap_or_else(|_| TodoList::new(HashMap::new()));
+
+                        let options = ScanOptions {
+                            next_id: next_id(&data_todo.items),
+                            no_rewrite: true,
+                            tracked: data_todo.items.clone(),
+                            strict: args.strict,
+                            jobs,
+                            ..Default::default()
+                        };
+                        let mut todo_items = get_todos(&[], None, &cfg, &options)?;
+                        carry_over(&mut todo_items, &data_todo.items);
+
+                        sort_todo_items(todo_items)
+                    } else {
+                        sort_todo_items(read_data_file(cfg.data_backend, &cfg.root)?.items)
+                    };
+
+                    todo_items
+                        .retain(|(_, item)| filter.matches(item) && matcher.is_match(&item.title));
+
+                    let (mut outbuf, is_stdout) = get_outbuf(out, &cfg, args.create_dirs, page)?;
+                    match format {
+                        OutputFormat::Markdown => {
+                            write_todo_items!(todo_items, outbuf, is_stdout, render)
+                        }
+                        OutputFormat::Json => write_todo_json(&todo_items, &mut outbuf)?,
+                        OutputFormat::MarkdownTable => {
+                            write_todo_table(&todo_items, &[], &render, is_stdout, &mut outbuf)?
+                        }
+                        OutputFormat::Csv => write_todo_csv(&todo_items, &[], &mut outbuf)?,
+                        OutputFormat::Sarif => write_todo_sarif(&todo_items, &mut outbuf)?,
+                        OutputFormat::Tree => write_todo_tree(
+                            &todo_items,
+                            is_stdout && std::io::stdout().is_terminal(),
+                            &mut outbuf,
+                        )?,
+                    }
+                    outbuf.flush().context("could not write the output")?;
+                }
+                TodoCommands::Show {
+                    id,
+                    context,
+                    format,
+                } => {
+                    let todo_list = read_data_file(cfg.data_backend, &cfg.root)?;
+                    let item = todo_list
+                        .items
+                        .get(&id)
+                        .with_context(|| format!("no TODO with id `{}`", id))?;
+
+                    let stdout = std::io::stdout();
+                    let mut handle = stdout.lock();
+
+                    if format == ShowFormat::Json {
+                        serde_json::to_writer_pretty(&mut handle, &TodoEntry { id: &id, item })
+                            .context("could not write JSON output")?;
+                        writeln!(handle)?;
+                        return Ok(std::process::ExitCode::SUCCESS);
+                    }
+
+                    writeln!(handle, "{}({}): {}", item.category, id, item.title.trim())?;
+                    writeln!(
+                        handle,
+                        "status: {}",
+                        if item.done { "done" } else { "open" }
+                    )?;
+                    if !item.labels.is_empty() {
+                        writeln!(handle, "labels: {}", item.labels.join(", "))?;
+                    }
+                    if let Some(at) = item.created_at {
+                        writeln!(handle, "created: {}", zone.format(at))?;
+                    }
+                    if let Some(at) = item.completed_at {
+                        writeln!(handle, "completed: {}", zone.format(at))?;
+                    }
+                    for note in &item.notes {
+                        writeln!(handle, "note: {}", note)?;
+                    }
+
+                    let location = match item.cell {
+                        Some(cell) => format!("{}:cell {}", display_path(&item.path), cell),
+                        None => display_path(&item.path),
+                    };
+                    let lines = match std::fs::read(cfg.root.join(&item.path)) {
+                        Ok(bytes) => source_lines_of(item, bytes, &cfg),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                            writeln!(
+                                handle,
+                                "location: {}:{} (file not found)",
+                                location, item.line
+                            )?;
+                            return Ok(std::process::ExitCode::SUCCESS);
+                        }
+                        Err(e) => Err(e.into()),
+                    };
+                    let lines = match lines {
+                        Ok(lines) => lines,
+                        Err(e) => {
+                            writeln!(
+                                handle,
+                                "location: {}:{} (could not read the file: {:#})",
+                                location, item.line, e
+                            )?;
+                            return Ok(std::process::ExitCode::SUCCESS);
+                        }
+                    };
+                    let lines = lines.iter().map(String::as_str).collect::<Vec<_>>();
+
+                    match find_todo_line(&lines, &id, item, &cfg.delimiters()) {
+                        Some(found) => {
+                            if found + 1 != item.line {
+                                writeln!(
+                                    handle,
+                                    "location: {}:{} (recorded at line {})",
+                                    location,
+                                    found + 1,
+                                    item.line
+                                )?;
+                            } else {
+                                writeln!(handle, "location: {}:{}", location, item.line)?;
+                            }
+
+                            writeln!(handle)?;
+
+                            let start = found.saturating_sub(context);
+                            let end = (found + context + 1).min(lines.len());
+                            let width = end.to_string().len();
+
+                            for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+                                writeln!(
+                                    handle,
+                                    "{} {:>width$} | {}",
+                                    if i == found { ">" } else { " " },
+                                    i + 1,
+                                    line,
+                                    width = width
+                                )?;
+                            }
+                        }
+                        None => writeln!(
+                            handle,
+                            "location: {}:{} (no longer found in the file)",
+                            location, item.line
+                        )?,
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(std::process::ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, removed when it goes
+    /// out of scope, so a failing assert does not leave it behind.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("mrdm-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = std::path::Path;
+
+        fn deref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<std::path::Path> for TempDir {
+        fn as_ref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A workspace scanning `src/*.rs` for `TODO` and `FIXME`, with these
+    /// files in it and nothing tracked yet.
+    fn workspace(name: &str, files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new(name);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join(".mrdm")).unwrap();
+        write_json_data_file(&dir, &TodoList::new(HashMap::new())).unwrap();
+        std::fs::write(dir.join(".git"), "").unwrap();
+        std::fs::write(
+            dir.join(CONFIG_PATH),
+            r#"{"patterns": ["TODO", "FIXME"], "include": ["src/*.rs"], "out": null}"#,
+        )
+        .unwrap();
+        for (path, content) in files {
+            std::fs::write(dir.join(path), content).unwrap();
+        }
+        dir
+    }
+
+    /// Runs `mrdm --root <dir> <args>`, logging through the test harness.
+    fn run_in(dir: &std::path::Path, args: &[&str]) -> Result<std::process::ExitCode> {
+        let _ = env_logger::builder().is_test(true).try_init();
+        let root = dir.to_str().unwrap();
+        let args = [&["mrdm", "--root", root, "--no-pager"], args].concat();
+        run(Cli::try_parse_from(args)?)
+    }
+
+    #[test]
+    fn test_regex() {
+        let re = create_regex(
+            vec!["TODO", "FIXME"],
+            &default_comment_markers(),
+            &IdDelimiters::default(),
+            false,
+        )
+        .unwrap();
+
+        let caps = re.captures("// TODO(6): test").unwrap();
+        assert_eq!(caps.name("category").unwrap().as_str(), "TODO");
+        assert_eq!(caps.name("title").unwrap().as_str(), "test");
+
+        let caps = re.captures("// FIXME(2): test").unwrap();
+        assert_eq!(caps.name("category").unwrap().as_str(), "FIXME");
+        assert_eq!(caps.name("id").unwrap().as_str(), "2");
+        assert_eq!(caps.name("title").unwrap().as_str(), "test");
+
+        let caps = re
+            .captures(
+                r#"
+            testing("// TODO: test");"#,
+            )
+            .is_none();
+
+        assert!(caps);
+    }
+
+    #[test]
+    fn test_regex_multi_part_category() {
+        let re = create_regex(
+            vec!["TODO", "TODO-SEC", "TODO.perf"],
+            &default_comment_markers(),
+            &IdDelimiters::default(),
+            false,
+        )
+        .unwrap();
+
+        let caps = re.captures("// TODO-SEC(2): audit").unwrap();
+        assert_eq!(caps.name("category").unwrap().as_str(), "TODO-SEC");
+        assert_eq!(caps.name("id").unwrap().as_str(), "2");
+        assert_eq!(caps.name("title").unwrap().as_str(), "audit");
+
+        let caps = re.captures("    // TODO.perf: cache this").unwrap();
+        assert_eq!(caps.name("category").unwrap().as_str(), "TODO.perf");
+        assert!(caps.name("id").is_none());
+
+        let caps = re.captures("// TODO: plain").unwrap();
+        assert_eq!(caps.name("category").unwrap().as_str(), "TODO");
+    }
+
+    #[test]
+    fn test_regex_literal_patterns() {
+        let re = create_regex(
+            vec!["C++TODO", "TODO*"],
+            &default_comment_markers(),
+            &IdDelimiters::default(),
+            false,
+        )
+        .unwrap();
+
+        let caps = re.captures("// C++TODO: literal").unwrap();
+        assert_eq!(caps.name("category").unwrap().as_str(), "C++TODO");
+
+        let caps = re.captures("// TODO*: star").unwrap();
+        assert_eq!(caps.name("category").unwrap().as_str(), "TODO*");
+
+        assert!(re.captures("// CCTODO: not a repetition").is_none());
+        assert!(re.captures("// TODOOO: not a repetition").is_none());
+    }
+
+    #[test]
+    fn test_regex_comment_markers() {
+        let markers = vec!["#".to_string(), "/*".to_string()];
+        let re = create_regex(vec!["TODO"], &markers, &IdDelimiters::default(), false).unwrap();
+
+        let caps = re.captures("x = 1  # TODO(4): python").unwrap();
+        assert_eq!(caps.name("marker").unwrap().as_str(), "#");
+        assert_eq!(caps.name("id").unwrap().as_str(), "4");
+
+        let caps = re.captures("/* TODO: block */").unwrap();
+        assert_eq!(caps.name("marker").unwrap().as_str(), "/*");
+        assert_eq!(
+            re.replace("/* TODO: block */", "$before$marker $category(7): $title"),
+            "/* TODO(7): block */"
+        );
+
+        assert!(re.captures("// TODO: not a marker here").is_none());
+        assert!(create_regex(vec!["TODO"], &[], &IdDelimiters::default(), false).is_err());
+    }
+
+    #[test]
+    fn test_regex_without_patterns() {
+        let markers = default_comment_markers();
+        for patterns in [vec![], vec![""], vec!["TODO", " "]] {
+            let err =
+                create_regex(patterns, &markers, &IdDelimiters::default(), false).unwrap_err();
+            assert!(err.to_string().starts_with("no patterns configured"));
+        }
+    }
+
+    #[test]
+    fn test_id_delimiters() {
+        let square = IdDelimiters::parse("[]").unwrap();
+        assert_eq!(square.wrap("3"), "[3]");
+        assert_eq!(IdDelimiters::parse(" #").unwrap().wrap("3"), " #3");
+        assert!(IdDelimiters::parse("").is_err());
+        assert!(IdDelimiters::parse("1").is_err());
+
+        let re = create_regex(vec!["TODO"], &default_comment_markers(), &square, false).unwrap();
+        assert_eq!(
+            re.captures("// TODO[3]: x")
+                .unwrap()
+                .name("id")
+                .unwrap()
+                .as_str(),
+            "3"
+        );
+        assert!(re.captures("// TODO(3): x").is_none());
+
+        let hash = IdDelimiters::parse(" #").unwrap();
+        let re = create_regex(vec!["TODO"], &default_comment_markers(), &hash, false).unwrap();
+        assert_eq!(
+            re.captures("// TODO #12: x")
+                .unwrap()
+                .name("id")
+                .unwrap()
+                .as_str(),
+            "12"
+        );
+    }
+
+    #[test]
+    fn test_id_prefix() {
+        let cfg = CliConfig {
+            id_prefix: "mrdm-".to_string(),
+            ..CliConfig::default()
+        };
+        let delimiters = cfg.delimiters();
+        assert_eq!(delimiters.wrap("0"), "(mrdm-0)");
+
+        let re = create_regex(vec!["TODO"], &cfg.comment_markers, &delimiters, false).unwrap();
+        for line in ["// TODO(mrdm-4): minted", "// TODO(4): by hand"] {
+            assert_eq!(re.captures(line).unwrap().name("id").unwrap().as_str(), "4");
+        }
+        assert_eq!(
+            re.replace("// TODO: x", "$before$marker $category(mrdm-0): $title"),
+            "// TODO(mrdm-0): x"
+        );
+
+        let item = todo_item("x", "src/a.rs", 1);
+        let lines = ["// TODO(mrdm-4): minted", "// TODO(5): by hand"];
+        assert_eq!(find_todo_line(&lines, "4", &item, &delimiters), Some(0));
+        assert_eq!(find_todo_line(&lines, "5", &item, &delimiters), Some(1));
+    }
+
+    #[test]
+    fn test_square_id_delimiters_round_trip() {
+        let dir = TempDir::new("square");
+        let path = dir.join("a.rs");
+        std::fs::write(&path, "fn main() {} // TODO: ship\n").unwrap();
+
+        let cfg = CliConfig {
+            id_delimiters: "[]".to_string(),
+            ..CliConfig::default()
+        };
+        let re =
+            create_regex(vec!["TODO"], &cfg.comment_markers, &cfg.delimiters(), false).unwrap();
+
+        let mut files = vec![scan_file(&path, &re, &cfg, &ScanStats::default(), None).unwrap()];
+        assign_ids(&mut files, 5);
+        write_back(&files[0], &cfg.root, &re, &cfg.delimiters()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn main() {} // TODO[5]: ship\n"
+        );
+
+        let rescanned = scan_file(&path, &re, &cfg, &ScanStats::default(), None).unwrap();
+        assert_eq!(rescanned.matches[0].id.as_deref(), Some("5"));
+        assert_eq!(rescanned.matches[0].item.title, "ship");
+    }
+
+    #[test]
+    fn test_scan_lines_parallel() {
+        let content = (0..50)
+            .map(|i| match i % 7 {
+                0 => format!("// TODO({}): keep {}", i, i),
+                3 => format!("let x = 1; // FIXME: fix {}", i),
+                _ => format!("let line_{} = {};", i, i),
+            })
+            .collect::<Vec<_>>();
+        let lines = content.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let cfg = CliConfig::default();
+        let re = create_regex(
+            vec!["TODO", "FIXME"],
+            &cfg.comment_markers,
+            &cfg.delimiters(),
+            false,
+        )
+        .unwrap();
+        let path = std::path::Path::new("src/big.rs");
+
+        let summary = |matches: Vec<TodoMatch>| {
+            matches
+                .into_iter()
+                .map(|m| (m.id, m.item.line, m.item.title))
+                .collect::<Vec<_>>()
+        };
+        let sequential = summary(scan_lines(&lines, 0, path, &re, &cfg));
+
+        assert_eq!(sequential.len(), 15);
+        for threads in [1, 3, 8, 64] {
+            assert_eq!(
+                summary(scan_lines_parallel(&lines, threads, path, &re, &cfg)),
+                sequential
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_back_keeps_line_endings() {
+        let cfg = CliConfig::default();
+        let re =
+            create_regex(vec!["TODO"], &cfg.comment_markers, &cfg.delimiters(), false).unwrap();
+
+        for (name, input, output) in [
+            ("last", "// TODO: x", "// TODO(0): x"),
+            ("newline", "// TODO: x\n", "// TODO(0): x\n"),
+            ("crlf", "a\r\n// TODO: x\r\nb", "a\r\n// TODO(0): x\r\nb"),
+        ] {
+            let dir = TempDir::new(&format!("endings-{}", name));
+            let path = dir.join("a.rs");
+            std::fs::write(&path, input).unwrap();
+
+            let mut files = vec![scan_file(&path, &re, &cfg, &ScanStats::default(), None).unwrap()];
+            assign_ids(&mut files, 0);
+            write_back(&files[0], &cfg.root, &re, &cfg.delimiters()).unwrap();
+
+            let written = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(written, output, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_mark_done_in_source() {
+        let dir = TempDir::new("mark-done");
+        let path = dir.join("a.rs");
+        std::fs::write(&path, "fn a() {}\n// TODO(3): ship\n// TODO(4): keep").unwrap();
+
+        let mut item = todo_item("ship", &path.to_string_lossy(), 2);
+        item.path = path.clone();
+        let cfg = CliConfig::default();
+
+        assert!(mark_done_in_source(&item, "3", "DONE", &cfg).unwrap());
+        assert!(!mark_done_in_source(&item, "9", "DONE", &cfg).unwrap());
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, "fn a() {}\n// DONE(3): ship\n// TODO(4): keep");
+    }
+
+    #[test]
+    fn test_presets_parse() {
+        for preset in <Preset as clap::ValueEnum>::value_variants() {
+            let cfg: CliConfig = serde_json::from_str(preset.config()).unwrap();
+            assert!(!cfg.comment_markers.is_empty(), "{:?}", preset);
+        }
+    }
+
+    #[test]
+    fn test_trim_title() {
+        let terminators = vec!["//".to_string(), ";".to_string()];
+
+        assert_eq!(trim_title("x // owner: bob", &terminators), "x");
+        assert_eq!(trim_title("a; b // c", &terminators), "a");
+        assert_eq!(trim_title("no terminator", &terminators), "no terminator");
+        assert_eq!(trim_title("kept // as is", &[]), "kept // as is");
+    }
+
+    fn todo_item(title: &str, path: &str, line: usize) -> TodoItem {
+        TodoItem {
+            title: title.to_string(),
+            category: "TODO".to_string(),
+            path: std::path::PathBuf::from(path),
+            line,
+            column: 4,
+            done: false,
+            raw: format!("// TODO: {}", title),
+            labels: vec![],
+            kept: false,
+            depends_on: vec![],
+            author: None,
+            notes: vec![],
+            cell: None,
+            created_at: None,
+            completed_at: None,
+            snoozed_until: None,
+        }
+    }
+
+    #[test]
+    fn test_split_labels() {
+        assert_eq!(
+            split_labels("ship [blocked]"),
+            ("ship", vec!["blocked".to_string()])
+        );
+        assert_eq!(
+            split_labels("ship [a][b] "),
+            ("ship", vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(split_labels("fix index[0]"), ("fix index[0]", vec![]));
+        assert_eq!(split_labels("empty []"), ("empty []", vec![]));
+        assert_eq!(split_labels("no labels"), ("no labels", vec![]));
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(
+            expand_braces("src/*.{rs,toml}"),
+            vec!["src/*.rs", "src/*.toml"]
+        );
+        assert_eq!(
+            expand_braces("{a,b{c,d}}/{x,y}"),
+            vec!["a/x", "a/y", "bc/x", "bc/y", "bd/x", "bd/y"]
+        );
+        assert_eq!(expand_braces("src/**/*.rs"), vec!["src/**/*.rs"]);
+        assert_eq!(expand_braces("{only}/{a,b"), vec!["{only}/{a,b"]);
+        assert_eq!(expand_braces("{only}/{a,b}"), vec!["{only}/a", "{only}/b"]);
+    }
+
+    #[test]
+    fn test_scan_files_expands_braces() {
+        let dir = TempDir::new("braces");
+        for name in ["a.rs", "b.toml", "c.md"] {
+            std::fs::write(dir.join(name), "// TODO: x\n").unwrap();
+        }
+
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.{{rs,toml}}", dir.display())],
+            ..CliConfig::default()
+        };
+        let (_, files) = scan_files(&[], None, &cfg, &Default::default()).unwrap();
+
+        let names = files
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.rs", "b.toml"]);
+    }
+
+    #[test]
+    fn test_ids_follow_include_order() {
+        let dir = TempDir::new("roots");
+        std::fs::create_dir_all(dir.join("vendor")).unwrap();
+        for name in ["a.rs", "z.rs", "vendor/m.rs"] {
+            std::fs::write(dir.join(name), "// TODO: x\n").unwrap();
+        }
+
+        // the second root overlaps the first, its files keep the first root
+        let cfg = CliConfig {
+            include: vec![
+                format!("{}/vendor/*.rs", dir.display()),
+                format!("{}/**/*.rs", dir.display()),
+            ],
+            ..CliConfig::default()
+        };
+
+        for _ in 0..5 {
+            let (_, mut files) = scan_files(&[], None, &cfg, &Default::default()).unwrap();
+            assign_ids(&mut files, 0);
+
+            let ids = files
+                .iter()
+                .flat_map(|file| &file.matches)
+                .map(|m| {
+                    let name = m.item.path.strip_prefix(&dir).unwrap().to_path_buf();
+                    (display_path(&name), m.id.clone().unwrap())
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                ids,
+                vec![
+                    ("vendor/m.rs".to_string(), "0".to_string()),
+                    ("a.rs".to_string(), "1".to_string()),
+                    ("z.rs".to_string(), "2".to_string()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_unmatched_include_is_strict_error() {
+        let cfg = CliConfig {
+            include: vec!["scr-does-not-exist/**/*.rs".to_string()],
+            ..CliConfig::default()
+        };
+
+        let (_, files) = scan_files(&[], None, &cfg, &Default::default()).unwrap();
+        assert!(files.is_empty());
+
+        let strict = ScanOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = scan_files(&[], None, &cfg, &strict).unwrap_err();
+        assert!(err.to_string().contains("`scr-does-not-exist/**/*.rs`"));
+    }
+
+    #[test]
+    fn test_parse_path_list() {
+        let list = "src/a.rs\n\n  # generated\nsrc/**/*.py  \n";
+
+        assert_eq!(
+            parse_path_list(list),
+            vec![
+                std::path::PathBuf::from("src/a.rs"),
+                std::path::PathBuf::from("src/**/*.py"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_has_allowed_extension() {
+        let extensions = vec!["rs".to_string(), "py".to_string()];
+
+        assert!(has_allowed_extension(
+            std::path::Path::new("src/main.rs"),
+            &extensions
+        ));
+        assert!(has_allowed_extension(
+            std::path::Path::new("a/b.py"),
+            &extensions
+        ));
+        assert!(!has_allowed_extension(
+            std::path::Path::new("README.md"),
+            &extensions
+        ));
+        assert!(!has_allowed_extension(
+            std::path::Path::new("Makefile"),
+            &extensions
+        ));
+        assert!(has_allowed_extension(std::path::Path::new("Makefile"), &[]));
+    }
+
+    #[test]
+    fn test_find_todo_line() {
+        let lines = vec![
+            "fn main() {",
+            "    // TODO(7): moved",
+            "}",
+            "",
+            "// TODO(8): other",
+        ];
+
+        let item = todo_item("moved", "src/a.rs", 2);
+        assert_eq!(
+            find_todo_line(&lines, "7", &item, &IdDelimiters::default()),
+            Some(1)
+        );
+
+        // the code drifted down and up since the scan
+        let item = todo_item("moved", "src/a.rs", 4);
+        assert_eq!(
+            find_todo_line(&lines, "7", &item, &IdDelimiters::default()),
+            Some(1)
+        );
+        let item = todo_item("other", "src/a.rs", 1);
+        assert_eq!(
+            find_todo_line(&lines, "8", &item, &IdDelimiters::default()),
+            Some(4)
+        );
+
+        assert_eq!(
+            find_todo_line(&lines, "9", &item, &IdDelimiters::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_windows_paths_use_forward_slashes() {
+        let item = todo_item("x", "src\\nested\\a.rs", 3);
+
+        assert_eq!(display_path(&item.path), "src/nested/a.rs");
+
+        let json = serde_json::to_value(&item).unwrap();
+        assert_eq!(json["path"], "src/nested/a.rs");
+
+        let mut out = Vec::new();
+        (|| -> Result<()> {
+            write_todo_items!([("0", &item)], out, true, RenderOptions::default());
+            Ok(())
+        })()
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "- [ ] TODO(0): x (src/nested/a.rs:3)\n"
+        );
+    }
+
+    fn scanned_file(path: &str, ids: &[Option<&str>]) -> ScannedFile {
+        ScannedFile {
+            path: std::path::PathBuf::from(path),
+            root: 0,
+            content: String::new(),
+            scan_only: false,
+            encoded: None,
+            matches: ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| TodoMatch {
+                    id: id.map(str::to_string),
+                    minted: false,
+                    item: todo_item("x", path, i + 1),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_assign_ids() {
+        // threads finish in any order, ids must not depend on it
+        let mut files = vec![
+            scanned_file("src/b.rs", &[None, Some("4")]),
+            scanned_file("src/a.rs", &[None, None]),
+        ];
+
+        assign_ids(&mut files, 2);
+
+        let ids = files
+            .iter()
+            .flat_map(|file| &file.matches)
+            .map(|m| {
+                (
+                    display_path(&m.item.path),
+                    m.item.line,
+                    m.id.clone().unwrap(),
+                    m.minted,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ids,
+            vec![
+                ("src/a.rs".to_string(), 1, "5".to_string(), true),
+                ("src/a.rs".to_string(), 2, "6".to_string(), true),
+                ("src/b.rs".to_string(), 1, "7".to_string(), true),
+                ("src/b.rs".to_string(), 2, "4".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_args() {
+        let filter = FilterArgs {
+            category: vec!["TODO".to_string()],
+            status: StatusFilter::Open,
+            label: vec![],
+            path_filter: Some(glob::Pattern::new("src/net/*").unwrap()),
+        };
+
+        assert!(filter.matches(&todo_item("x", "./src/net/tcp.rs", 1)));
+        assert!(!filter.matches(&todo_item("x", "src/net/http/mod.rs", 1)));
+        assert!(!filter.matches(&todo_item("x", "src/cli.rs", 1)));
+
+        let mut done = todo_item("x", "src/net/tcp.rs", 1);
+        done.done = true;
+        assert!(!filter.matches(&done));
+    }
+
+    #[test]
+    fn test_link_style() {
+        let item = todo_item("x", "src/a.rs", 12);
+        let location = |link_style, link_template: Option<&str>| {
+            let render = RenderOptions {
+                link_style,
+                link_template: link_template.map(str::to_string),
+                ..Default::default()
+            };
+            render.location(&item, false)
+        };
+
+        assert_eq!(location(LinkStyle::Github, None), "[link](src/a.rs#L12)");
+        assert_eq!(location(LinkStyle::Gitlab, None), "[link](src/a.rs#L12)");
+        assert_eq!(
+            location(LinkStyle::Bitbucket, None),
+            "[link](src/a.rs#lines-12)"
+        );
+        assert_eq!(
+            location(LinkStyle::Custom, Some("#line-{line}")),
+            "[link](src/a.rs#line-12)"
+        );
+        assert_eq!(
+            RenderOptions::default().location(&item, true),
+            "(src/a.rs:12)"
+        );
+
+        assert!(LinkStyle::Custom.validate(Some("#n{line}")).is_ok());
+        assert!(LinkStyle::Custom.validate(Some("#n")).is_err());
+        assert!(LinkStyle::Custom.validate(None).is_err());
+        assert!(LinkStyle::Bitbucket.validate(None).is_ok());
+    }
+
+    #[test]
+    fn test_collapse_done() {
+        let mut done = todo_item("shipped", "src/a.rs", 1);
+        done.done = true;
+        let open = todo_item("pending", "src/b.rs", 2);
+        let items = [("0", &done), ("1", &open)];
+
+        let render = RenderOptions {
+            collapse_done: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        (|| -> Result<()> {
+            write_todo_items!(items, out, false, render);
+            Ok(())
+        })()
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "- [ ] TODO(1): pending [link](src/b.rs#L2)\n\
+             \n<details>\n<summary>Done (1)</summary>\n\n\
+             - [x] ~~TODO(0): shipped~~ [link](src/a.rs#L1)\n\
+             \n</details>\n"
+        );
+
+        // stdout keeps every item in place
+        let mut out = Vec::new();
+        (|| -> Result<()> {
+            write_todo_items!(items, out, true, render);
+            Ok(())
+        })()
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "- [x] TODO(0): shipped (src/a.rs:1)\n- [ ] TODO(1): pending (src/b.rs:2)\n"
+        );
+    }
+
+    #[test]
+    fn test_carry_over() {
+        let mut kept = todo_item("x", "src/a.rs", 1);
+        kept.done = true;
+        kept.kept = true;
+        kept.notes = vec!["spoke with Alice".to_string()];
+        let mut removed = todo_item("y", "src/a.rs", 2);
+        removed.done = true;
+
+        let prev = HashMap::from([("0".to_string(), kept), ("1".to_string(), removed)]);
+        let mut curr = HashMap::from([
+            ("0".to_string(), todo_item("x", "src/a.rs", 1)),
+            ("1".to_string(), todo_item("y", "src/a.rs", 2)),
+        ]);
+
+        carry_over(&mut curr, &prev);
+        assert!(curr["0"].done && curr["0"].kept);
+        assert_eq!(curr["0"].notes, vec!["spoke with Alice".to_string()]);
+        assert!(!curr["1"].done);
+        assert!(diff_todos(&prev, &curr).undone == vec!["1".to_string()]);
+    }
+
+    #[test]
+    fn test_render_options() {
+        let mut item = todo_item("x", "src/a.rs", 1);
+        item.done = true;
+
+        let render = RenderOptions {
+            checkbox: CheckboxStyle {
+                bullet: "*".to_string(),
+                done: "X".to_string(),
+            },
+            ..Default::default()
+        };
+        assert!(render.checkbox.validate().is_ok());
+
+        let mut out = Vec::new();
+        (|| -> Result<()> {
+            write_todo_items!([("0", &item)], out, true, render);
+            Ok(())
+        })()
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "* [X] TODO(0): x (src/a.rs:1)\n"
+        );
+
+        let render = RenderOptions {
+            flat: true,
+            ..render
+        };
+        let mut out = Vec::new();
+        (|| -> Result<()> {
+            write_todo_items!([("0", &item)], out, false, render);
+            Ok(())
+        })()
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "* [X] TODO(0): x\n");
+
+        let invalid = CheckboxStyle {
+            bullet: "1.".to_string(),
+            done: "x".to_string(),
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert!(ColorChoice::Always.enabled(false));
+        assert!(!ColorChoice::Never.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+
+        assert_eq!(paint("TODO", YELLOW, true), "\x1b[33mTODO\x1b[0m");
+        assert_eq!(paint("TODO", YELLOW, false), "TODO");
+
+        // colors only ever reach stdout, never an output file
+        let item = todo_item("x", "src/a.rs", 1);
+        let render = RenderOptions {
+            color: true,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        (|| -> Result<()> {
+            write_todo_items!([("0", &item)], out, false, render);
+            Ok(())
+        })()
+        .unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains('\x1b'));
+    }
+
+    #[test]
+    fn test_assign_placeholder_ids() {
+        let mut files = vec![
+            scanned_file("src/b.rs", &[None, Some("4")]),
+            scanned_file("src/a.rs", &[None]),
+        ];
+
+        assign_placeholder_ids(&mut files);
+
+        let ids = files
+            .iter()
+            .flat_map(|file| &file.matches)
+            .map(|m| (m.id.clone().unwrap(), m.minted))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ids,
+            vec![
+                ("new-1".to_string(), false),
+                ("new-2".to_string(), false),
+                ("4".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_lost_ids() {
+        let mut files = vec![scanned_file("src/a.rs", &[None, None, Some("2"), None])];
+        files[0].matches[0].item.title = "lost".to_string();
+        files[0].matches[1].item.title = "dup".to_string();
+        files[0].matches[3].item.title = "dup".to_string();
+
+        let tracked = HashMap::from([
+            ("3".to_string(), todo_item("lost", "./src/a.rs", 7)),
+            ("4".to_string(), todo_item("dup", "src/a.rs", 2)),
+            ("2".to_string(), todo_item("x", "src/a.rs", 3)),
+        ]);
+
+        let lost = find_lost_ids(&files, &tracked);
+        assert_eq!(
+            lost,
+            vec![LostId {
+                file: 0,
+                index: 0,
+                id: "3".to_string()
+            }]
+        );
+
+        restore_lost_ids(&mut files, &lost, true);
+        assert_eq!(files[0].matches[0].id.as_deref(), Some("3"));
+        assert!(files[0].matches[0].minted);
+        assert!(find_lost_ids(&files, &tracked).is_empty());
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let mut item = todo_item("x", "src/a.rs", 1);
+        // only serialized once `--blame` filled it in
+        item.author = Some("Ada".to_string());
+        item.created_at = Some(chrono::Utc::now());
+        item.completed_at = item.created_at;
+        item.snoozed_until = chrono::NaiveDate::from_ymd_opt(2025, 9, 1);
+        // only serialized for notebook TODOs
+        item.cell = Some(3);
+        let entry = serde_json::to_value(TodoEntry {
+            id: "1",
+            item: &item,
+        })
+        .unwrap();
+        let mut keys = entry
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut all = TODO_FIELDS.map(String::from).to_vec();
+        keys.sort();
+        all.sort();
+        assert_eq!(keys, all);
+
+        let fields = parse_fields(&["line".to_string(), "id".to_string()], OutputFormat::Json);
+        assert_eq!(fields.unwrap(), vec!["line", "id"]);
+        assert!(parse_fields(&["owner".to_string()], OutputFormat::Json).is_err());
+        assert!(parse_fields(&["id".to_string()], OutputFormat::Markdown).is_err());
+        assert_eq!(
+            parse_fields(&["cell".to_string()], OutputFormat::Csv).unwrap(),
+            ["cell"]
+        );
+        assert_eq!(field_text("1", &item, "cell"), "3");
+
+        let mut out = vec![];
+        write_todo_json_fields(&[("1".to_string(), item)], &["line", "id"], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.find("\"line\"").unwrap() < out.find("\"id\"").unwrap());
+        assert!(!out.contains("title"));
+    }
+
+    #[test]
+    fn test_sort_todo_items() {
+        let items = ["10", "2", "b", "0", "a"]
+            .iter()
+            .map(|id| (id.to_string(), todo_item("x", "src/main.rs", 1)));
+
+        let ids = sort_todo_items(items)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, vec!["0", "2", "10", "a", "b"]);
+    }
+
+    #[test]
+    fn test_done_summary() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            format_id_ranges(&ids(&["17", "15", "3", "16", "new-1"])),
+            "3, 15–17, new-1"
+        );
+
+        let summary = DoneSummary {
+            marked_done: ids(&["7", "3"]),
+            removed: ids(&["5"]),
+            recreated: vec![("9".to_string(), "14".to_string())],
+            new: ids(&["15", "16", "17"]),
+            ..Default::default()
+        };
+        assert_eq!(
+            summary.render(),
+            "Marked done: 3, 7. Removed: 5. Recreated: 9→14. New: 15–17."
+        );
+        assert_eq!(DoneSummary::default().render(), "Nothing changed.");
+    }
+
+    #[test]
+    fn test_diff_todos() {
+        let mut done = todo_item("done", "src/a.rs", 2);
+        done.done = true;
+
+        let prev = HashMap::from([
+            ("0".to_string(), todo_item("kept", "src/a.rs", 1)),
+            ("1".to_string(), done.clone()),
+            ("2".to_string(), todo_item("gone", "src/a.rs", 3)),
+            ("3".to_string(), todo_item("finished", "src/a.rs", 4)),
+        ]);
+
+        let mut finished = todo_item("finished", "src/a.rs", 4);
+        finished.done = true;
+
+        let curr = HashMap::from([
+            ("0".to_string(), todo_item("kept", "src/a.rs", 1)),
+            ("1".to_string(), todo_item("done", "src/a.rs", 2)),
+            ("3".to_string(), finished),
+            ("4".to_string(), todo_item("new", "src/a.rs", 5)),
+        ]);
+
+        assert_eq!(
+            diff_todos(&prev, &curr),
+            TodoDiff {
+                added: vec!["4".to_string()],
+                deleted: vec!["2".to_string()],
+                undone: vec!["1".to_string()],
+                completed: vec!["3".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_todo_tree() {
+        let todo_items = vec![
+            ("0".to_string(), todo_item("parse", "src/cli/args.rs", 3)),
+            ("1".to_string(), todo_item("split", "src/main.rs", 10)),
+            ("2".to_string(), todo_item("clean up", "src/main.rs", 20)),
+        ];
+
+        let mut boxed = Vec::new();
+        write_todo_tree(&todo_items, true, &mut boxed).unwrap();
+        assert_eq!(
+            String::from_utf8(boxed).unwrap(),
+            ". (3)
+└── src (3)
+    ├── cli (1)
+    │   └── args.rs (1)
+    │       └── [ ] TODO(0): parse (line 3)
+    └── main.rs (2)
+        ├── [ ] TODO(1): split (line 10)
+        └── [ ] TODO(2): clean up (line 20)
+"
+        );
+
+        let mut plain = Vec::new();
+        write_todo_tree(&todo_items[..1], false, &mut plain).unwrap();
+        assert_eq!(
+            String::from_utf8(plain).unwrap(),
+            ". (1)
+src (1)
+  cli (1)
+    args.rs (1)
+      [ ] TODO(0): parse (line 3)
+"
+        );
+    }
+
+    #[test]
+    fn test_markdown_title() {
+        assert_eq!(markdown_title(" refactor a | b "), "refactor a \\| b");
+        assert_eq!(
+            markdown_title("split\n   across\n\nlines"),
+            "split across lines"
+        );
+
+        let item = todo_item("refactor a | b", "src/a.rs", 1);
+        let render = RenderOptions {
+            flat: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        (|| -> Result<()> {
+            write_todo_items!([("0", &item)], out, true, render);
+            Ok(())
+        })()
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "- [ ] TODO(0): refactor a \\| b\n"
+        );
+    }
+
+    #[test]
+    fn test_data_backend() {
+        assert!(DataBackend::Json.validate().is_ok());
+        assert_eq!(
+            DataBackend::Sqlite.validate().is_ok(),
+            cfg!(feature = "sqlite")
+        );
+    }
+
+    #[test]
+    fn test_title_matcher() {
+        let matcher = title_matcher("memory leak", false).unwrap();
+        assert!(matcher.is_match("fix the Memory Leak in the pool"));
+        assert!(!matcher.is_match("memory is leaking"));
+
+        // plain queries are literal
+        assert!(title_matcher("a.b", false).unwrap().is_match("a.b"));
+        assert!(!title_matcher("a.b", false).unwrap().is_match("axb"));
+
+        let matcher = title_matcher("^(fix|clean)\\b", true).unwrap();
+        assert!(matcher.is_match("Clean up the parser"));
+        assert!(!matcher.is_match("unclean"));
+        assert!(title_matcher("(", true).is_err());
+    }
+
+    #[test]
+    fn test_repeated_pattern() {
+        let cli = Cli::try_parse_from([
+            "mrdm",
+            "todo",
+            "list",
+            "-p",
+            "TODO,FIXME",
+            "-p",
+            "HACK",
+            "-p",
+            "TODO",
+        ])
+        .unwrap();
+        let Some(Commands::Todo(todo_args)) = cli.command else {
+            panic!("expected the todo command");
+        };
+        let TodoCommands::List { pattern, .. } = todo_args.command else {
+            panic!("expected todo list");
+        };
+
+        let cfg = CliConfig::default();
+        assert_eq!(resolve_pattern(&pattern, &cfg), ["TODO", "FIXME", "HACK"]);
+        assert_eq!(resolve_pattern(&[], &cfg), cfg.patterns);
+    }
+
+    #[test]
+    fn test_strip_id_lines() {
+        let cfg = CliConfig::default();
+        let re = create_regex(
+            vec!["TODO", "FIXME"],
+            &cfg.comment_markers,
+            &cfg.delimiters(),
+            false,
+        )
+        .unwrap();
+        let content = "// TODO(3): parse\nlet a = 1; // FIXME(4): check a\n// TODO: untracked\n";
+        let lines = content.lines().collect::<Vec<_>>();
+        let path = std::path::Path::new("src/a.rs");
+
+        let file = ScannedFile {
+            path: path.to_path_buf(),
+            root: 0,
+            content: content.to_string(),
+            scan_only: false,
+            encoded: None,
+            matches: scan_lines(&lines, 0, path, &re, &cfg),
+        };
+
+        assert_eq!(
+            strip_id_lines(&file, &re),
+            HashMap::from([
+                (0, "// TODO: parse".to_string()),
+                (1, "let a = 1; // FIXME: check a".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let cfg = parse_config(
+            config::File::from_str(
+                r#"{"patterns": ["TODO", "FIXME"], "include": ["lib/**/*.rs"]}"#,
+                config::FileFormat::Json,
+            ),
+            None,
+        )
+        .unwrap();
+        assert_eq!(cfg.patterns, ["TODO", "FIXME"]);
+        assert_eq!(cfg.include, ["lib/**/*.rs"]);
+        assert_eq!(cfg.comment_markers, default_comment_markers());
+
+        assert!(parse_config(
+            config::File::from_str(r#"{"patterns": "#, config::FileFormat::Json),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_profiles() {
+        let json = r#"{
+            "patterns": ["TODO"],
+            "include": ["**/*.rs"],
+            "out": null,
+            "extensions": ["rs"],
+            "profiles": {
+                "default": { "include": ["src/**/*.rs"] },
+                "CI": { "patterns": ["TODO", "FIXME"], "max_title_width": 20 }
+            }
+        }"#;
+        let parse = |profile| {
+            parse_config(
+                config::File::from_str(json, config::FileFormat::Json),
+                profile,
+            )
+        };
+
+        let default = parse(None).unwrap();
+        assert_eq!(default.include, ["src/**/*.rs"]);
+        assert_eq!(default.patterns, ["TODO"]);
+
+        let ci = parse(Some("ci")).unwrap();
+        assert_eq!(ci.include, ["**/*.rs"]);
+        assert_eq!(ci.patterns, ["TODO", "FIXME"]);
+        assert_eq!(ci.max_title_width, Some(20));
+        assert_eq!(ci.extensions, ["rs"]);
+
+        let err = parse(Some("docs")).unwrap_err();
+        assert_eq!(err.to_string(), "unknown profile `docs`");
+    }
+
+    #[test]
+    fn test_category_column() {
+        let cfg = CliConfig::default();
+        let re =
+            create_regex(vec!["TODO"], &cfg.comment_markers, &cfg.delimiters(), false).unwrap();
+        let lines = ["// TODO: first", "let é = 1; // TODO(2): second"];
+
+        let matches = scan_lines(&lines, 0, std::path::Path::new("src/a.rs"), &re, &cfg);
+        assert_eq!(matches[0].item.column, 4);
+        assert_eq!(matches[1].item.column, 15);
+    }
+
+    #[test]
+    fn test_badge() {
+        let style = BadgeStyle::default();
+        assert_eq!(style.color(9), "#4c1");
+        assert_eq!(style.color(10), "#dfb317");
+        assert_eq!(style.color(50), "#e05d44");
+
+        let svg = style.render(37);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("aria-label=\"TODOs: 37\""));
+        assert!(svg.contains("fill=\"#dfb317\""));
+
+        let style = BadgeStyle {
+            label: "a<b".to_string(),
+            ..Default::default()
+        };
+        assert!(style.render(0).contains(">a&lt;b<"));
+        assert!(BadgeStyle {
+            yellow: 60,
+            ..Default::default()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_ignore_case_dedupe() {
+        let lines = [
+            "// TODO: Fix",
+            "// todo: fix",
+            "// TODO: fix",
+            "// TODO: other",
+        ];
+        let path = std::path::Path::new("src/a.rs");
+
+        let scan = |ignore_case: bool| {
+            let cfg = CliConfig {
+                ignore_case,
+                ..Default::default()
+            };
+            let re = create_regex(
+                vec!["TODO"],
+                &cfg.comment_markers,
+                &cfg.delimiters(),
+                ignore_case,
+            )
+            .unwrap();
+
+            scan_lines(&lines, 0, path, &re, &cfg)
+                .into_iter()
+                .enumerate()
+                .map(|(i, m)| (i.to_string(), m.item))
+                .collect::<Vec<_>>()
+        };
+
+        let items = scan(true);
+        assert_eq!(items.len(), 4);
+        let deduped = dedupe_todo_items(items, true);
+        assert_eq!(
+            deduped
+                .iter()
+                .map(|(_, item)| (item.category.as_str(), item.title.as_str()))
+                .collect::<Vec<_>>(),
+            [("TODO", "Fix"), ("TODO", "other")]
+        );
+
+        // `todo` is no match, `Fix` and `fix` stay apart
+        let items = scan(false);
+        assert_eq!(items.len(), 3);
+        assert_eq!(dedupe_todo_items(items, false).len(), 3);
+    }
+
+    #[test]
+    fn test_disallowed_categories() {
+        let content = "// TODO: ok\n// TDOO: typo\nlet s = \"// HACK: in a string\";\n// FIXME(3): not allowed\n// Note: prose\n";
+        let file = ScannedFile {
+            path: std::path::PathBuf::from("src/a.rs"),
+            root: 0,
+            content: content.to_string(),
+            scan_only: false,
+            encoded: None,
+            matches: vec![],
+        };
+
+        let mut cfg = CliConfig::default();
+        assert!(disallowed_categories(std::slice::from_ref(&file), &cfg)
+            .unwrap()
+            .is_empty());
+
+        cfg.allowed_categories = vec!["TODO".to_string()];
+        assert_eq!(
+            disallowed_categories(&[file], &cfg).unwrap(),
+            [(0, 2, "TDOO".to_string()), (0, 4, "FIXME".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_depends() {
+        assert_eq!(parse_depends("do X after #3", ""), ["3"]);
+        assert_eq!(
+            parse_depends("ship it, depends: 3, 5,3 and After #7", ""),
+            ["3", "5", "7"]
+        );
+        assert_eq!(parse_depends("after #mrdm-4", "mrdm-"), ["4"]);
+        assert!(parse_depends("thereafter #2", "").is_empty());
+        assert!(parse_depends("nothing to wait for", "").is_empty());
+
+        let mut item = todo_item("after #1", "src/a.rs", 1);
+        item.depends_on = vec!["1".to_string()];
+        let open = HashSet::from(["1".to_string()]);
+        assert!(is_blocked(&item, &open));
+        assert!(!is_blocked(&item, &HashSet::new()));
+    }
+
+    #[test]
+    fn test_write_todo_table() {
+        let mut done = todo_item("refactor a | b", "src/a.rs", 3);
+        done.done = true;
+        let todo_items = vec![
+            ("0".to_string(), done),
+            ("1".to_string(), todo_item("parse", "src/b.rs", 7)),
+        ];
+        let render = RenderOptions::default();
+
+        let mut out = Vec::new();
+        write_todo_table(&todo_items, &[], &render, false, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "| Status | Category | ID | Title | Location |\n\
+             | --- | --- | --- | --- | --- |\n\
+             | done | TODO | 0 | refactor a \\| b | [link](src/a.rs#L3) |\n\
+             | open | TODO | 1 | parse | [link](src/b.rs#L7) |\n"
+        );
+
+        let fields = parse_fields(
+            &[
+                "id".to_string(),
+                "depends_on".to_string(),
+                "location".to_string(),
+            ],
+            OutputFormat::MarkdownTable,
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        write_todo_table(&todo_items[1..], &fields, &render, true, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "| ID | Depends on | Location |\n| --- | --- | --- |\n| 1 |  | src/b.rs:7 |\n"
+        );
+        assert!(parse_fields(&["location".to_string()], OutputFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_migrate_todo_list() {
+        let mut todo: TodoList = serde_json::from_str(
+            r#"{"items": {"3": {"title": "ship after #1 [blocked]", "category": "TODO",
+                "path": "src/a.rs", "line": 4, "done": false}}}"#,
+        )
+        .unwrap();
+        assert_eq!(todo.schema_version, 0);
+
+        todo.migrate().unwrap();
+        assert_eq!(todo.schema_version, SCHEMA_VERSION);
+        let item = &todo.items["3"];
+        assert_eq!(item.title, "ship after #1");
+        assert_eq!(item.labels, ["blocked"]);
+        assert_eq!(item.depends_on, ["1"]);
+
+        todo.schema_version = SCHEMA_VERSION + 1;
+        assert!(todo.migrate().is_err());
+
+        // reading upgrades in memory only, without the lock to write it
+        let dir = TempDir::new("migrate");
+        std::fs::create_dir_all(dir.join(".mrdm")).unwrap();
+        let old = r#"{"items": {"3": {"title": "x", "category": "TODO", "path": "a.rs", "line": 1, "done": false}}}"#;
+        std::fs::write(dir.join(OUT_PATH), old).unwrap();
+        let read = read_json_data_file(&dir);
+        let on_disk = std::fs::read_to_string(dir.join(OUT_PATH)).unwrap();
+        assert_eq!(read.unwrap().schema_version, SCHEMA_VERSION);
+        assert_eq!(on_disk, old);
+    }
+
+    #[test]
+    fn test_parse_blame_authors() {
+        let porcelain = "\
+4b825dc642cb6eb9a060e54bf8d69288fbee4904 3 4 1
+author Ada Lovelace
+author-mail <ada@example.com>
+summary add parser
+filename src/a.rs
+\t// TODO(1): parse
+0000000000000000000000000000000000000000 9 12 1
+author Not Committed Yet
+filename src/a.rs
+\t// TODO: new
+6f1ed002ab5595859014ebf0951522d9825e4cba3a4d62e94e7ce7b4f1afa4a1 2 20 1
+author Grace Hopper
+filename src/a.rs
+\t// TODO(2): sha-256
+";
+        assert_eq!(
+            parse_blame_authors(porcelain),
+            HashMap::from([
+                (4, "Ada Lovelace".to_string()),
+                (12, "Not Committed Yet".to_string()),
+                (20, "Grace Hopper".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_scan_archive() {
+        let dir = TempDir::new("archive");
+        let archive = dir.join("src.tar.gz");
+
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+            std::fs::File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        ));
+        for (path, content) in [
+            ("src/main.rs", "// TODO(4): tracked\n// TODO: new\n"),
+            ("docs/notes.md", "// TODO: not included\n"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let mut files = scan_archive(&archive, &[], &CliConfig::default()).unwrap();
+        assign_placeholder_ids(&mut files);
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, std::path::Path::new("src/main.rs"));
+        assert_eq!(
+            files[0]
+                .matches
+                .iter()
+                .map(|m| (m.id.as_deref().unwrap(), m.item.line))
+                .collect::<Vec<_>>(),
+            [("4", 1), ("new-1", 2)]
+        );
+    }
+
+    #[test]
+    fn test_scan_leaves_files_untouched() {
+        let dir = TempDir::new("scan");
+        let source = "// TODO(7): tracked\n// TODO: new one\n";
+        std::fs::write(dir.join("a.rs"), source).unwrap();
+
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            ..CliConfig::default()
+        };
+        let list = scan(&cfg).unwrap();
+        let after = std::fs::read_to_string(dir.join("a.rs")).unwrap();
+
+        assert_eq!(after, source);
+        assert_eq!(list.schema_version, SCHEMA_VERSION);
+        assert_eq!(list.items["7"].title, "tracked");
+        assert_eq!(list.items["new-1"].title, "new one");
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let mut item = todo_item("split a, b", "src\\a.rs", 3);
+        item.raw = "// TODO(0): split a, b \"now\"\nand later".to_string();
+        item.labels = vec!["x".to_string(), "y".to_string()];
+        let todo_items = vec![("0".to_string(), item)];
+
+        let mut out = Vec::new();
+        write_todo_csv(&todo_items, &[], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "id,category,title,path,line,done\n0,TODO,\"split a, b\",src/a.rs,3,false\n"
+        );
+
+        let fields = parse_fields(
+            &["raw".to_string(), "labels".to_string()],
+            OutputFormat::Csv,
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        write_todo_csv(&todo_items, &fields, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "raw,labels\n\"// TODO(0): split a, b \"\"now\"\"\nand later\",x;y\n"
+        );
+    }
+
+    #[test]
+    fn test_max_title_width() {
+        let render = RenderOptions {
+            max_title_width: Some(6),
+            ..Default::default()
+        };
+        assert_eq!(render.title("naïve façade"), "naïve…");
+        assert_eq!(render.title("short"), "short");
+        assert_eq!(render.title("a | b c d"), "a \\| b…");
+        assert_eq!(
+            RenderOptions::default().title("naïve façade"),
+            "naïve façade"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_hooks() {
+        let dir = TempDir::new("hooks");
+        let log = dir.join("log");
+
+        let record = Some(format!("echo \"$1 $MRDM_DATA_FILE\" >> {}", log.display()));
+        run_hooks(
+            &[
+                ("post_scan", &record),
+                ("post_done", &None),
+                ("failing", &Some("exit 3".to_string())),
+            ],
+            DataBackend::Json,
+            std::path::Path::new(""),
+        );
+        let logged = std::fs::read_to_string(&log).unwrap();
+
+        assert_eq!(logged, format!("{0} {0}\n", OUT_PATH));
+    }
+
+    #[test]
+    fn test_hash_tracking() {
+        let dir = TempDir::new("hash");
+        let source = "// TODO: Parse  args\n// TODO: parse args\n// TODO(3): kept\n";
+        std::fs::write(dir.join("a.rs"), source).unwrap();
+
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            tracking: Tracking::Hash,
+            ..CliConfig::default()
+        };
+        let items = get_todos(&[], None, &cfg, &Default::default()).unwrap();
+        let after = std::fs::read_to_string(dir.join("a.rs")).unwrap();
+        let path = display_path(&dir.join("a.rs"));
+
+        assert_eq!(after, source);
+        let hash = content_hash("parse args", &path);
+        assert_eq!(items[&hash].line, 1);
+        assert_eq!(items[&format!("{}-2", hash)].line, 2);
+        assert_eq!(items["3"].title, "kept");
+
+        assert_eq!(
+            content_hash("a", "src/a.rs"),
+            content_hash(" A ", "src/a.rs")
+        );
+        assert_ne!(content_hash("a", "src/a.rs"), content_hash("a", "src/b.rs"));
+        assert!(content_hash("a", "src/a.rs").parse::<usize>().is_err());
+    }
+
+    #[test]
+    fn test_read_only_files_are_skipped() {
+        let dir = TempDir::new("readonly");
+        std::fs::write(dir.join("a.rs"), "// TODO: locked\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "// TODO: open\n").unwrap();
+        let mut permissions = std::fs::metadata(dir.join("a.rs")).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(dir.join("a.rs"), permissions).unwrap();
+
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            ..CliConfig::default()
+        };
+        let items = get_todos(&[], None, &cfg, &Default::default()).unwrap();
+        let (a, b) = (
+            std::fs::read_to_string(dir.join("a.rs")).unwrap(),
+            std::fs::read_to_string(dir.join("b.rs")).unwrap(),
+        );
+        let leftover = dir.join("a.tmp").exists();
+
+        assert_eq!(a, "// TODO: locked\n");
+        assert_eq!(b, "// TODO(1): open\n");
+        assert!(!leftover);
+        assert_eq!(items["new-1"].title, "locked");
+        assert_eq!(items["1"].title, "open");
+    }
+
+    #[test]
+    fn test_notebook_code() {
+        let notebook = r##"{"cells": [
+            {"cell_type": "markdown", "source": ["# TODO: not code\n"]},
+            {"cell_type": "code", "source": ["import os\n", "# TODO: load the data\n"]},
+            {"cell_type": "code", "source": "# FIXME: plot it"}
+        ]}"##;
+        let cfg = CliConfig {
+            patterns: vec!["TODO".to_string(), "FIXME".to_string()],
+            comment_markers: vec!["#".to_string()],
+            ..CliConfig::default()
+        };
+        let re = create_regex(
+            vec!["TODO", "FIXME"],
+            &cfg.comment_markers,
+            &cfg.delimiters(),
+            false,
+        )
+        .unwrap();
+
+        let path = std::path::Path::new("nb.ipynb");
+        let matches = scan_embedded(path, notebook, &re, &cfg).unwrap();
+        let found = matches
+            .iter()
+            .map(|m| (m.item.title.as_str(), m.item.cell, m.item.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![("load the data", Some(2), 2), ("plot it", Some(3), 1)]
+        );
+        assert_eq!(
+            RenderOptions::default().location(&matches[0].item, true),
+            "(nb.ipynb:cell 2:2)"
+        );
+
+        assert!(scan_embedded(std::path::Path::new("a.py"), notebook, &re, &cfg).is_none());
+        assert!(scan_embedded(path, "not json", &re, &cfg)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let now = chrono::Utc::now();
+        let earlier = now - chrono::TimeDelta::try_days(10).unwrap();
+
+        let mut done = todo_item("shipped", "src/a.rs", 1);
+        done.done = true;
+        let mut reopened = todo_item("reopened", "src/a.rs", 2);
+        reopened.created_at = Some(earlier);
+        reopened.completed_at = Some(earlier);
+        let mut items = HashMap::from([
+            ("0".to_string(), done),
+            ("1".to_string(), reopened),
+            ("2".to_string(), todo_item("new", "src/a.rs", 3)),
+        ]);
+        let tracked = HashSet::from(["0".to_string(), "1".to_string()]);
+
+        stamp_transitions(&mut items, &tracked, now);
+        assert_eq!(items["0"].created_at, None);
+        assert_eq!(items["0"].completed_at, Some(now));
+        assert_eq!(items["1"].created_at, Some(earlier));
+        assert_eq!(items["1"].completed_at, None);
+        assert_eq!(items["2"].created_at, Some(now));
+
+        let week = parse_duration("7d").unwrap();
+        assert!(is_changed_since(&items["0"], now - week));
+        assert!(!is_changed_since(&items["1"], now - week));
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            chrono::TimeDelta::try_days(14).unwrap()
+        );
+        assert_eq!(
+            parse_duration("24h").unwrap(),
+            chrono::TimeDelta::try_days(1).unwrap()
+        );
+        for invalid in ["", "7", "d", "7y", "-1d", "1.5h"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_invalid_include_pattern_is_skipped() {
+        let dir = TempDir::new("badglob");
+        std::fs::write(dir.join("a.rs"), "// TODO: x\n").unwrap();
+
+        let cfg = CliConfig {
+            include: vec![
+                "src/***/a.rs".to_string(),
+                format!("{}/*.rs", dir.display()),
+            ],
+            ..CliConfig::default()
+        };
+        let scanned =
+            scan_files(&[], None, &cfg, &Default::default()).map(|(_, files)| files.len());
+        let strict = ScanOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let failed = scan_files(&[], None, &cfg, &strict).is_err();
+
+        assert_eq!(scanned.unwrap(), 1);
+        assert!(failed);
+    }
+
+    #[test]
+    fn test_sarif() {
+        let mut done = todo_item("shipped", "src/a.rs", 1);
+        done.done = true;
+        let mut fixme = todo_item("leaks", "./src/b.rs", 7);
+        fixme.category = "FIXME".to_string();
+        let todo_items = vec![
+            ("0".to_string(), done),
+            ("1".to_string(), todo_item("parse args", "src/a.rs", 3)),
+            ("2".to_string(), fixme),
+        ];
+
+        let mut out = Vec::new();
+        write_todo_sarif(&todo_items, &mut out).unwrap();
+        let log: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "FIXME");
+        assert_eq!(run["tool"]["driver"]["rules"][1]["id"], "TODO");
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["ruleId"], "TODO");
+        assert_eq!(results[0]["ruleIndex"], 1);
+        assert_eq!(results[0]["message"]["text"], "parse args");
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/b.rs");
+        assert_eq!(location["region"]["startLine"], 7);
+        assert_eq!(location["region"]["startColumn"], 4);
+        assert_eq!(results[1]["partialFingerprints"]["mrdmId"], "2");
+    }
+
+    #[test]
+    fn test_split_snooze() {
+        let date = chrono::NaiveDate::from_ymd_opt(2025, 9, 1).unwrap();
+        assert_eq!(
+            split_snooze("revisit caching @snooze(2025-09-01)"),
+            ("revisit caching".into(), Some(date))
+        );
+        assert_eq!(
+            split_snooze("revisit @snooze(2025-09-01) caching [perf]"),
+            ("revisit caching [perf]".into(), Some(date))
+        );
+        assert_eq!(
+            split_snooze("later @snooze(2025-13-01)"),
+            ("later @snooze(2025-13-01)".into(), None)
+        );
+
+        let mut item = todo_item("x", "src/a.rs", 1);
+        item.snoozed_until = Some(date);
+        assert!(is_snoozed(&item, date.pred_opt().unwrap()));
+        assert!(!is_snoozed(&item, date));
+        assert!(!is_snoozed(&todo_item("x", "src/a.rs", 1), date));
+    }
+
+    #[test]
+    fn test_regex_literals_before_the_comment() {
+        let re = create_regex(
+            vec!["TODO"],
+            &default_comment_markers(),
+            &IdDelimiters::default(),
+            false,
+        )
+        .unwrap();
+        let comment = |line: &str| {
+            let caps = re.captures(line)?;
+            (!follows_url_scheme(&caps["before"], &caps["marker"]))
+                .then(|| (caps["before"].to_string(), caps["title"].to_string()))
+        };
+
+        assert_eq!(
+            comment(r#"let u = "https://x"; // TODO: y"#),
+            Some((r#"let u = "https://x"; "#.to_string(), "y".to_string()))
+        );
+        assert_eq!(
+            comment(r#"let q = '"'; // TODO: y"#),
+            Some((r#"let q = '"'; "#.to_string(), "y".to_string()))
+        );
+        assert_eq!(
+            comment(r#"let s = "a \" // TODO: no"; // TODO: y"#)
+                .unwrap()
+                .1,
+            "y"
+        );
+        assert_eq!(comment("if c == '/' { // TODO: y }").unwrap().1, "y }");
+        assert_eq!(
+            comment("fn f<'a>(x: &'a str) {} // TODO: y").unwrap().1,
+            "y"
+        );
+        assert_eq!(comment("    // TODO: x").unwrap().0, "    ");
+        assert_eq!(comment("see https://TODO: not a comment"), None);
+        assert!(follows_url_scheme("x = svn+ssh:", "//"));
+        assert!(!follows_url_scheme("x = 1:", "//"));
+        assert!(!follows_url_scheme("x = a:", "#"));
+    }
+
+    #[test]
+    fn test_minted_ids_are_recorded() {
+        let dir = TempDir::new("minted");
+        std::fs::write(dir.join("a.rs"), "// TODO(4): old\n// TODO: new\n").unwrap();
+
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            ..CliConfig::default()
+        };
+        let options = ScanOptions::default();
+        let items = get_todos(&[], None, &cfg, &options).unwrap();
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(*options.stats.minted.lock().unwrap(), vec!["5".to_string()]);
+    }
+
+    #[test]
+    fn test_change_budget() {
+        let dir = TempDir::new("budget");
+        for name in ["a.rs", "b.rs"] {
+            std::fs::write(dir.join(name), "// TODO: x\n// TODO: y\n").unwrap();
+        }
+        let read = || ["a.rs", "b.rs"].map(|name| std::fs::read_to_string(dir.join(name)).unwrap());
+
+        let mut cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            max_changes: Some(1),
+            ..CliConfig::default()
+        };
+        let over_files = get_todos(&[], None, &cfg, &Default::default()).is_err();
+        let untouched = read();
+
+        cfg.max_changes = Some(2);
+        cfg.max_new_ids = Some(3);
+        let over_ids = get_todos(&[], None, &cfg, &Default::default()).is_err();
+
+        let forced = ScanOptions {
+            force: true,
+            ..Default::default()
+        };
+        let items = get_todos(&[], None, &cfg, &forced).unwrap();
+        let rewritten = read();
+
+        assert!(over_files && over_ids);
+        assert_eq!(untouched, ["// TODO: x\n// TODO: y\n"; 2]);
+        assert_eq!(items.len(), 4);
+        assert_eq!(rewritten[1], "// TODO(2): x\n// TODO(3): y\n");
+    }
+
+    #[test]
+    fn test_count_authors() {
+        let mut items =
+            ["a", "b", "c", "d"].map(|title| (title.to_string(), todo_item(title, "a.rs", 1)));
+        items[0].1.author = Some("Grace".to_string());
+        items[1].1.author = Some("Ada".to_string());
+        items[2].1.author = Some("Grace".to_string());
+
+        let counts = count_authors(&items);
+        assert_eq!(
+            counts,
+            [
+                ("Grace".to_string(), 2),
+                ("Ada".to_string(), 1),
+                ("unknown".to_string(), 1)
+            ]
+        );
+
+        let mut outbuf = vec![];
+        write_author_counts(&counts, OutputFormat::Markdown, &mut outbuf).unwrap();
+        assert_eq!(
+            String::from_utf8(outbuf).unwrap(),
+            "| Author | Open |\n| --- | --- |\n| Grace | 2 |\n| Ada | 1 |\n| unknown | 1 |\n"
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        let dir = TempDir::new("aliases");
+        let path = dir.join("a.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "patterns": ["TODO"],
+                "include": ["src/**/*.rs"],
+                "out": null,
+                "aliases": {
+                    "report": "todo list --format json",
+                    "Fixmes": "report -p FIXME",
+                    "loop": "again",
+                    "again": "loop"
+                }
+            }"#,
+        )
+        .unwrap();
+        let parse = |alias: &str| {
+            Cli::try_parse_from_with_aliases([
+                "mrdm",
+                "--config",
+                path.to_str().unwrap(),
+                alias,
+                "--flat",
+            ])
+        };
+
+        let fixmes = parse("fixmes");
+        let recursive = parse("loop");
+        let unknown = parse("nope");
+
+        let Some(Commands::Todo(todo_args)) = fixmes.unwrap().command else {
+            panic!("expected the todo command");
+        };
+        let TodoCommands::List {
+            format,
+            pattern,
+            flat,
+            ..
+        } = todo_args.command
+        else {
+            panic!("expected todo list");
+        };
+        assert!(matches!(format, OutputFormat::Json));
+        assert_eq!(pattern, ["FIXME"]);
+        assert!(flat);
+
+        assert!(recursive
+            .unwrap_err()
+            .to_string()
+            .contains("alias `loop` expands to itself"));
+        assert_eq!(
+            unknown.unwrap_err().kind(),
+            clap::error::ErrorKind::InvalidSubcommand
+        );
+    }
+
+    #[test]
+    fn test_config_layers() {
+        let root = TempDir::new("layers");
+        let inner = root.join("crates/inner");
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(&inner).unwrap();
+        std::fs::write(
+            root.join(CONFIG_PATH),
+            r#"{
+                "patterns": ["TODO", "FIXME"],
+                "include": ["**/*.rs"],
+                "out": null,
+                "extensions": ["rs"],
+                "max_title_width": 40,
+                "done_markers": { "todo": "DONE" }
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            inner.join(CONFIG_PATH),
+            r#"{
+                "patterns": ["HACK"],
+                "include": ["src/**/*.rs"],
+                "extensions": ["py"],
+                "max_title_width": 60,
+                "done_markers": { "fixme": "FIXED" },
+                "merge_lists": ["patterns"]
+            }"#,
+        )
+        .unwrap();
+
+        let layers = config_layers(&inner);
+        let cfg = read_config_layers(&layers, None);
+        let outside = config_layers(&std::env::temp_dir());
+
+        assert_eq!(layers, [root.join(CONFIG_PATH), inner.join(CONFIG_PATH)]);
+        let cfg = cfg.unwrap();
+        assert_eq!(cfg.patterns, ["TODO", "FIXME", "HACK"]);
+        assert_eq!(cfg.include, ["src/**/*.rs"]);
+        assert_eq!(cfg.extensions, ["py"]);
+        assert_eq!(cfg.max_title_width, Some(60));
+        assert_eq!(cfg.done_markers.len(), 2);
+        assert!(!outside.contains(&root.join(CONFIG_PATH)));
+    }
+
+    #[test]
+    fn test_regex_doc_comments() {
+        let re = create_regex(
+            vec!["TODO"],
+            &default_comment_markers(),
+            &IdDelimiters::default(),
+            false,
+        )
+        .unwrap();
+
+        for (line, marker, injected) in [
+            (
+                "/// TODO: document this",
+                "///",
+                "/// TODO(0): document this",
+            ),
+            (
+                "    //! TODO: crate docs",
+                "//!",
+                "    //! TODO(0): crate docs",
+            ),
+            ("// TODO: plain", "//", "// TODO(0): plain"),
+        ] {
+            let caps = re.captures(line).unwrap();
+            assert_eq!(&caps["marker"], marker);
+            assert_eq!(&caps["category"], "TODO");
+            assert_eq!(
+                re.replace(line, "$before$marker $category(0): $title"),
+                injected
+            );
+        }
+        assert_eq!(
+            &re.captures("    //! TODO: crate docs").unwrap()["before"],
+            "    "
+        );
+    }
+
+    #[test]
+    fn test_ignore_directives() {
+        let cfg = CliConfig::default();
+        let re =
+            create_regex(vec!["TODO"], &cfg.comment_markers, &cfg.delimiters(), false).unwrap();
+        let lines = [
+            "// TODO: real",
+            r#"let fixture = "x"; // TODO: fake // mrdm:ignore"#,
+            "// mrdm:ignore-next-line",
+            "// TODO: also fake",
+            "// TODO: real again",
+            "// TODO: mrdm:ignored is not the directive",
+        ];
+
+        let titles = |matches: Vec<TodoMatch>| {
+            matches
+                .into_iter()
+                .map(|m| (m.item.line, m.item.title))
+                .collect::<Vec<_>>()
+        };
+        let expected = [
+            (1, "real".to_string()),
+            (5, "real again".to_string()),
+            (6, "mrdm:ignored is not the directive".to_string()),
+        ];
+        let path = std::path::Path::new("a.rs");
+
+        assert_eq!(titles(scan_lines(&lines, 0, path, &re, &cfg)), expected);
+        // the directive and the line it ignores end up in different chunks
+        assert_eq!(
+            titles(scan_lines_parallel(&lines, 2, path, &re, &cfg)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_null_records() {
+        let items = vec![
+            ("1".to_string(), todo_item("a b", "src/with space.rs", 3)),
+            ("2".to_string(), todo_item("c", "b.rs", 7)),
+        ];
+
+        let mut outbuf = vec![];
+        write_todo_null(&items, &[], &mut outbuf).unwrap();
+        assert_eq!(
+            outbuf,
+            b"1\0src/with space.rs\x003\0TODO\0a b\0\x002\0b.rs\x007\0TODO\0c\0\0"
+        );
+
+        let mut outbuf = vec![];
+        write_todo_null(&items[..1], &["path"], &mut outbuf).unwrap();
+        assert_eq!(outbuf, b"src/with space.rs\0\0");
+    }
+
+    #[test]
+    fn test_display_zone() {
+        let at = "2026-03-01T23:30:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap();
+
+        assert_eq!(DisplayZone::parse(None).unwrap(), DisplayZone::Local);
+        assert_eq!(
+            DisplayZone::parse(Some("Local")).unwrap(),
+            DisplayZone::Local
+        );
+        assert_eq!(
+            DisplayZone::parse(Some("Asia/Tokyo")).unwrap().format(at),
+            "2026-03-02 08:30 +09:00"
+        );
+        assert_eq!(
+            DisplayZone::parse(Some("UTC")).unwrap().format(at),
+            "2026-03-01 23:30 +00:00"
+        );
+        assert!(DisplayZone::parse(Some("Mars/Olympus")).is_err());
+    }
+
+    #[test]
+    fn test_history() {
+        let dir = TempDir::new("history");
+        let path = dir.join("history.jsonl");
+        let at = "2026-03-01T23:30:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap();
+
+        let summary = DoneSummary {
+            marked_done: vec!["1".to_string()],
+            reopened: vec!["2".to_string()],
+            recreated: vec![("3".to_string(), "7".to_string())],
+            new: vec!["8".to_string()],
+            ..Default::default()
+        };
+        let created = HistoryEntry::new(at, HistoryEvent::Created, "8");
+        append_history(&path, [created.clone()]).unwrap();
+        append_history(&path, summary.history(at)).unwrap();
+        let history = read_history(&path);
+
+        let history = history.unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0], created);
+        assert_eq!(
+            (history[1].event, history[1].from, history[1].to),
+            (
+                HistoryEvent::Done,
+                Some(ItemState::Open),
+                Some(ItemState::Done)
+            )
+        );
+        assert_eq!(history[2].event, HistoryEvent::Undone);
+        assert_eq!(history[3].previous_id.as_deref(), Some("3"));
+
+        let utc = DisplayZone::parse(Some("UTC")).unwrap();
+        assert_eq!(
+            history[3].render(utc),
+            "2026-03-01 23:30 +00:00  reindexed 7 (- → open), was 3"
+        );
+        assert!(read_history(std::path::Path::new("no/such/history.jsonl"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_regex_whole_word_categories() {
+        let markers = default_comment_markers();
+        let re = create_regex(
+            vec!["TODO", "BUG!"],
+            &markers,
+            &IdDelimiters::default(),
+            false,
+        )
+        .unwrap();
+
+        assert!(re.captures("// TODOey: x").is_none());
+        assert!(re.captures("// TODOlist: items").is_none());
+        assert_eq!(&re.captures("// TODO(3): x").unwrap()["id"], "3");
+        assert_eq!(&re.captures("// BUG!: x").unwrap()["category"], "BUG!");
+
+        let underscore = IdDelimiters::parse("_").unwrap();
+        let re = create_regex(vec!["TODO"], &markers, &underscore, false).unwrap();
+        assert_eq!(&re.captures("// TODO_3: x").unwrap()["id"], "3");
+
+        // a word character after `$category` must not make it another group
+        let injected = inject_id("// TODO: x", "3", &re, &underscore);
+        assert_eq!(injected, "// TODO_3: x");
+        let captures = re.captures(&injected).unwrap();
+        assert_eq!((&captures["category"], &captures["id"]), ("TODO", "3"));
+    }
+
+    #[test]
+    fn test_source_encoding() {
+        let dir = TempDir::new("encoding");
+        std::fs::write(dir.join("a.rs"), b"// TODO: caf\xe9\r\n// ol\xe9 \xff\n").unwrap();
+        std::fs::write(dir.join("b.rs"), b"\0\x01// TODO: binary\n").unwrap();
+
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            encoding: Some("latin1".to_string()),
+            ..CliConfig::default()
+        };
+        let items = get_todos(&[], None, &cfg, &Default::default());
+        let written = std::fs::read(dir.join("a.rs")).unwrap();
+
+        let items = items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items["0"].title, "café");
+        assert_eq!(written, b"// TODO(0): caf\xe9\r\n// ol\xe9 \xff\n");
+
+        // `done --id` renames comments in the same encoding
+        let path = dir.join("c.rs");
+        std::fs::write(&path, b"// TODO(3): caf\xe9\r\n// ol\xe9 \xff\n").unwrap();
+        let mut item = todo_item("café", &path.to_string_lossy(), 1);
+        item.path = path.clone();
+        let renamed = mark_done_in_source(&item, "3", "DONE", &cfg);
+        let written = std::fs::read(&path).unwrap();
+        assert!(renamed.unwrap());
+        assert_eq!(written, b"// DONE(3): caf\xe9\r\n// ol\xe9 \xff\n");
+
+        assert_eq!(parse_encoding(None).unwrap(), encoding_rs::UTF_8);
+        assert!(parse_encoding(Some("utf-16le")).is_err());
+        assert!(parse_encoding(Some("klingon")).is_err());
+    }
+
+    #[test]
+    fn test_recreate_item() {
+        let mut items = HashMap::new();
+        for (id, line) in [("0", 1), ("2", 3), ("1", 2)] {
+            items.insert(id.to_string(), todo_item(id, "a.rs", line));
+        }
+        items.get_mut("2").unwrap().notes = vec!["keep me".to_string()];
+        items.remove("1");
+
+        // two items left, the old `len()` would have handed out 2 again
+        assert_eq!(recreate_item(&mut items, "2"), "3");
+        assert_eq!(items.len(), 3);
+        assert_eq!(items["2"].title, "2");
+        assert_eq!(items["3"].title, "2");
+        assert_eq!(items["3"].notes, ["keep me"]);
+
+        assert_eq!(recreate_item(&mut items, "0"), "4");
+        assert_eq!(items["4"].line, 1);
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now = "2024-05-20T12:00:00Z"
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap();
+        let ago = |days: i64, secs: i64| {
+            relative_time(
+                now - chrono::Duration::days(days) - chrono::Duration::seconds(secs),
+                now,
+            )
+        };
+        assert_eq!(ago(0, 30), "just now");
+        assert_eq!(ago(0, 60), "1 minute ago");
+        assert_eq!(ago(0, 7200), "2 hours ago");
+        assert_eq!(ago(3, 0), "3 days ago");
+        assert_eq!(ago(-14, 0), "in 2 weeks");
+        assert_eq!(ago(65, 0), "2 months ago");
+        assert_eq!(ago(-400, 0), "in 1 year");
+
+        let mut item = todo_item("x", "src/a.rs", 1);
+        item.created_at = Some(now - chrono::Duration::days(3));
+        item.snoozed_until = Some("2024-06-03".parse().unwrap());
+        let mut render = RenderOptions::default();
+        assert_eq!(render.dates(&item), "");
+        render.dates = Some(DateStyle::Relative(now));
+        assert_eq!(
+            render.dates(&item),
+            " created 3 days ago, snoozed until in 1 week"
+        );
+        render.dates = Some(DateStyle::Absolute(DisplayZone::Named(
+            chrono_tz::Asia::Tokyo,
+        )));
+        assert_eq!(
+            render.dates(&item),
+            " created 2024-05-17 21:00 +09:00, snoozed until 2024-06-03"
+        );
+    }
+
+    #[test]
+    fn test_dry_run_diff() {
+        let dir = TempDir::new("dry-run");
+        let source = "fn a() {}\n// TODO: new\n// TODO(4): old\n";
+        std::fs::write(dir.join("a.rs"), source).unwrap();
+
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            ..CliConfig::default()
+        };
+        let options = ScanOptions {
+            next_id: 5,
+            dry_run: true,
+            ..Default::default()
+        };
+        let items = get_todos(&[], None, &cfg, &options);
+        let written = std::fs::read_to_string(dir.join("a.rs")).unwrap();
+
+        let items = items.unwrap();
+        assert_eq!(items["5"].title, "new");
+        assert_eq!(written, source);
+        let path = display_path(&dir.join("a.rs"));
+        assert_eq!(
+            options.stats.previews.lock().unwrap().as_slice(),
+            [format!(
+                "--- a/{path}\n+++ b/{path}\n@@ -1,3 +1,3 @@\n fn a() {{}}\n-// TODO: new\n+// TODO(5): new\n // TODO(4): old\n"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let now = chrono::Utc::now();
+        let mut items = vec![];
+        for (id, category, path, days) in [
+            ("1", "TODO", "b.rs", Some(2)),
+            ("2", "FIXME", "a.rs", None),
+            ("10", "BUG", "a.rs", Some(5)),
+        ] {
+            let mut item = todo_item(id, path, 1);
+            item.category = category.to_string();
+            item.created_at = days.map(|days| now - chrono::Duration::days(days));
+            items.push((id.to_string(), item));
+        }
+        let patterns = ["FIXME", "TODO"].map(str::to_string);
+        let order = |key: SortKey, reverse| {
+            let mut items = items.clone();
+            key.sort(&mut items, &patterns, reverse);
+            items.into_iter().map(|(id, _)| id).collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(SortKey::Id, false), ["1", "2", "10"]);
+        assert_eq!(order(SortKey::Id, true), ["10", "2", "1"]);
+        assert_eq!(order(SortKey::File, false), ["2", "10", "1"]);
+        assert_eq!(order(SortKey::Priority, false), ["2", "1", "10"]);
+        assert_eq!(order(SortKey::Category, false), ["10", "2", "1"]);
+        assert_eq!(order(SortKey::Created, false), ["10", "1", "2"]);
+        assert_eq!(order(SortKey::Created, true), ["1", "10", "2"]);
+    }
+
+    #[test]
+    fn test_jobs() {
+        let order = std::sync::Mutex::new(vec![]);
+        let tasks = (0..5)
+            .map(|n| {
+                let order = &order;
+                move || {
+                    order.lock().unwrap().push(n);
+                    n * 10
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(run_jobs(tasks.clone(), Some(1)), [0, 10, 20, 30, 40]);
+        assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3, 4]);
+        assert_eq!(run_jobs(tasks.clone(), Some(2)), [0, 10, 20, 30, 40]);
+        assert_eq!(run_jobs(tasks, None), [0, 10, 20, 30, 40]);
+
+        let dir = TempDir::new("jobs");
+        for name in ["a", "b", "c"] {
+            std::fs::write(dir.join(format!("{}.rs", name)), "// TODO: x\n// TODO: y\n").unwrap();
+        }
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            ..CliConfig::default()
+        };
+        let scan = |jobs| {
+            let options = ScanOptions {
+                no_rewrite: true,
+                jobs,
+                ..Default::default()
+            };
+            let items = sort_todo_items(get_todos(&[], None, &cfg, &options).unwrap());
+            serde_json::to_value(items).unwrap()
+        };
+        let (sequential, parallel) = (scan(Some(1)), scan(None));
+
+        assert_eq!(sequential.as_array().unwrap().len(), 6);
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn test_root() {
+        let dir = TempDir::new("root");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join(".mrdm")).unwrap();
+        std::fs::write(dir.join(".git"), "").unwrap();
+        std::fs::write(
+            dir.join(CONFIG_PATH),
+            r#"{"patterns": ["TODO"], "include": ["src/*.rs"]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/a.rs"), "// TODO: x\n").unwrap();
+        let current_dir = std::env::current_dir().unwrap();
+
+        let scan = || -> Result<_> {
+            let cfg = get_config_at(Some(&dir), None, None)?;
+            let items = get_todos(&[], None, &cfg, &Default::default())?;
+            write_data_file(cfg.data_backend, &cfg.root, &TodoList::new(items))?;
+            read_data_file(cfg.data_backend, &cfg.root)
+        };
+        let todo = scan();
+        let written = std::fs::read_to_string(dir.join("src/a.rs")).unwrap();
+        let stored = dir.join(OUT_PATH).is_file();
+        let missing = get_config_at(Some(&dir.join("missing")), None, None).is_err();
+
+        let todo = todo.unwrap();
+        assert_eq!(todo.items["0"].path, std::path::Path::new("src/a.rs"));
+        assert_eq!(written, "// TODO(0): x\n");
+        assert!(stored);
+        assert!(missing);
+        assert_eq!(std::env::current_dir().unwrap(), current_dir);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_data() {
+        let dir = TempDir::new("sqlite");
+        std::fs::create_dir_all(dir.join(".mrdm")).unwrap();
+        let titles = |todo: &TodoList| {
+            let mut titles = todo
+                .items
+                .iter()
+                .map(|(id, item)| format!("{}: {}", id, item.title))
+                .collect::<Vec<_>>();
+            titles.sort();
+            titles
+        };
+        let mut items = HashMap::new();
+        for id in ["0", "1", "2"] {
+            items.insert(id.to_string(), todo_item(id, "a.rs", 1));
+        }
+
+        // without a database yet, the json data file is read
+        write_json_data_file(&dir, &TodoList::new(items.clone())).unwrap();
+        let fallback = sqlite_data::read(&dir).map(|todo| titles(&todo));
+
+        sqlite_data::write(&dir, &TodoList::new(items.clone())).unwrap();
+        items.remove("1");
+        items.get_mut("2").unwrap().title = "changed".to_string();
+        let written = sqlite_data::write(&dir, &TodoList::new(items));
+        let read = sqlite_data::read(&dir).map(|todo| titles(&todo));
+
+        assert_eq!(fallback.unwrap(), ["0: 0", "1: 1", "2: 2"]);
+        written.unwrap();
+        assert_eq!(read.unwrap(), ["0: 0", "2: changed"]);
+    }
+
+    #[test]
+    fn test_exit_on_empty() {
+        let dir = workspace("exit-on-empty", &[("src/a.rs", "fn a() {}\n")]);
+        let list = ["todo", "list", "--exit-on-empty", "4", "--out", "out.md"];
+
+        assert_eq!(
+            run_in(&dir, &list).unwrap(),
+            std::process::ExitCode::from(4)
+        );
+
+        std::fs::write(dir.join("src/a.rs"), "// TODO: x\n").unwrap();
+        assert_eq!(
+            run_in(&dir, &list).unwrap(),
+            std::process::ExitCode::SUCCESS
+        );
+
+        // the filters apply first
+        let fixmes = [&list[..], &["-p", "FIXME"]].concat();
+        assert_eq!(
+            run_in(&dir, &fixmes).unwrap(),
+            std::process::ExitCode::from(4)
+        );
+    }
+
+    #[test]
+    fn test_notes_survive_scans() {
+        let dir = workspace("notes", &[("src/a.rs", "// TODO: x\n")]);
+        let out = ["--out", "out.md"];
+
+        run_in(&dir, &[&["todo", "done"], &out[..]].concat()).unwrap();
+        run_in(&dir, &["todo", "note", "0", " remember the cache "]).unwrap();
+        // like any other write, a note fails while another command holds the lock
+        let lock = DataLock::acquire(&dir).unwrap();
+        assert!(run_in(&dir, &["todo", "note", "0", "lost"]).is_err());
+        drop(lock);
+        std::fs::write(dir.join("src/a.rs"), "fn a() {}\n// TODO(0): x\n").unwrap();
+        run_in(&dir, &[&["todo", "done"], &out[..]].concat()).unwrap();
+        run_in(&dir, &[&["todo", "done", "--id", "0"], &out[..]].concat()).unwrap();
+
+        let todo = read_json_data_file(&dir).unwrap();
+        let item = &todo.items["0"];
+        assert_eq!(item.notes, ["remember the cache"]);
+        assert_eq!(item.line, 2);
+        assert!(item.done);
+        assert!(!dir.join(LOCK_PATH).exists());
+    }
+
+    #[test]
+    fn test_source_lines_of() {
+        let latin1 = CliConfig {
+            encoding: Some("latin1".to_string()),
+            ..CliConfig::default()
+        };
+        let item = todo_item("café", "a.rs", 1);
+        let bytes = b"// TODO(0): caf\xe9\nfn a() {}\n".to_vec();
+
+        let lines = source_lines_of(&item, bytes.clone(), &latin1).unwrap();
+        assert_eq!(lines, ["// TODO(0): café", "fn a() {}"]);
+        let err = source_lines_of(&item, bytes, &CliConfig::default()).unwrap_err();
+        assert!(format!("{:#}", err).contains("not UTF-8"));
+
+        let mut item = todo_item("plot it", "nb.ipynb", 2);
+        item.cell = Some(3);
+        let notebook = r##"{"cells": [
+            {"cell_type": "code", "source": ["import os\n"]},
+            {"cell_type": "markdown", "source": ["# TODO: not code\n"]},
+            {"cell_type": "code", "source": "x = 1\n# FIXME(0): plot it"}
+        ]}"##;
+        let lines = source_lines_of(&item, notebook.into(), &CliConfig::default()).unwrap();
+        assert_eq!(lines, ["x = 1", "# FIXME(0): plot it"]);
+
+        for format in ["markdown", "text", "json"] {
+            assert!(Cli::try_parse_from(["mrdm", "todo", "show", "0", "--format", format]).is_ok());
+        }
+        assert!(Cli::try_parse_from(["mrdm", "todo", "show", "0", "--format", "csv"]).is_err());
+    }
+
+    #[test]
+    fn test_scan_tar() {
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+            vec![],
+            flate2::Compression::default(),
+        ));
+        for (path, content) in [
+            ("./src/a.rs", "// TODO: a\n"),
+            ("src/nested/b.rs", "// TODO: not matched by `*`\n"),
+            ("src/c.rs", "fn c() {}\n// TODO: c\n"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        let gzipped = tar.into_inner().unwrap().finish().unwrap();
+
+        let cfg = CliConfig {
+            include: vec!["src/*.rs".to_string()],
+            ..CliConfig::default()
+        };
+        let reader = flate2::read::GzDecoder::new(gzipped.as_slice());
+        let files = scan_tar(reader, std::path::Path::new("src.tar.gz"), &[], &cfg).unwrap();
+
+        let found = files
+            .iter()
+            .flat_map(|file| &file.matches)
+            .map(|m| (display_path(&m.item.path), m.item.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [("src/a.rs".to_string(), 1), ("src/c.rs".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_dependency_problems() {
+        let mut fixme = todo_item("after #1 and #2", "a.rs", 3);
+        fixme.category = "FIXME".to_string();
+        fixme.depends_on = vec!["1".to_string(), "2".to_string(), "4".to_string()];
+        let open = todo_item("open", "a.rs", 1);
+        let mut tracked = HashMap::new();
+        tracked.insert("1".to_string(), todo_item("done", "a.rs", 1));
+        tracked.get_mut("1").unwrap().done = true;
+
+        let problems = dependency_problems(&[("3", &fixme), ("4", &open)], &tracked);
+        assert_eq!(
+            problems,
+            [
+                "a.rs:3: FIXME(3) depends on #1, which is already done",
+                "a.rs:3: FIXME(3) depends on #2, which does not exist",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_lines_prefilter() {
+        let cfg = CliConfig {
+            comment_markers: vec!["//".to_string(), "/*".to_string(), "*".to_string()],
+            ignore_case: true,
+            ..CliConfig::default()
+        };
+        let re = create_regex(vec!["TODO"], &cfg.comment_markers, &cfg.delimiters(), true).unwrap();
+        let lines = [
+            "fn a() {}",
+            "// todo: lowercase",
+            "/// Todo: doc comment",
+            "    //! TODO(4): crate docs",
+            "/* TODO: block */",
+            " * toDo: continued block",
+            "let todo = 1; // not a category",
+            "todo: no marker",
+            "x // ToDo: trailing",
+        ];
+
+        // the lines skipped for having no marker are lines the regex rejects
+        let found = scan_lines(&lines, 0, std::path::Path::new("a.rs"), &re, &cfg)
+            .into_iter()
+            .map(|m| m.item.line - 1)
+            .collect::<Vec<_>>();
+        let matched = (0..lines.len())
+            .filter(|i| re.is_match(lines[*i]))
+            .collect::<Vec<_>>();
+        assert_eq!(found, matched);
+        assert_eq!(found, [1, 2, 3, 4, 5, 8]);
+    }
+
+    #[test]
+    fn test_data_lock() {
+        let dir = TempDir::new("lock");
+        let path = dir.join(LOCK_PATH);
+
+        let lock = DataLock::acquire(&dir).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        let held = DataLock::acquire(&dir).err().unwrap().to_string();
+        assert!(
+            held.contains(&format!("pid {}", std::process::id())),
+            "{}",
+            held
+        );
+
+        // dropping the guard releases the lock
+        drop(lock);
+        assert!(!path.exists());
+        drop(DataLock::acquire(&dir).unwrap());
+
+        // a lock without a pid is never taken over
+        std::fs::write(&path, "").unwrap();
+        assert!(DataLock::acquire(&dir).is_err());
+
+        // the lock of a process that has exited is
+        if cfg!(target_os = "linux") {
+            std::fs::write(&path, u32::MAX.to_string()).unwrap();
+            let lock = DataLock::acquire(&dir).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(&path).unwrap(),
+                std::process::id().to_string()
+            );
+            drop(lock);
+            assert!(!path.exists());
+        }
+    }
+
+    #[test]
+    fn test_reconcile_reopens() {
+        let dir = workspace("reconcile", &[("src/a.rs", "// TODO: x\n")]);
+        let reconcile = ["todo", "done", "--reconcile", "--out", "out.md"];
+
+        run_in(&dir, &reconcile).unwrap();
+        std::fs::write(dir.join("src/a.rs"), "fn a() {}\n").unwrap();
+        run_in(&dir, &reconcile).unwrap();
+        assert!(read_json_data_file(&dir).unwrap().items["0"].done);
+
+        // back under its id, with another title
+        std::fs::write(dir.join("src/a.rs"), "fn a() {}\n// TODO(0): y\n").unwrap();
+        run_in(&dir, &reconcile).unwrap();
+
+        let todo = read_json_data_file(&dir).unwrap();
+        assert_eq!(todo.items.len(), 1);
+        let item = &todo.items["0"];
+        assert!(!item.done);
+        assert_eq!(item.title, "y");
+        assert_eq!(item.line, 2);
+    }
+
+    #[test]
+    fn test_output_on_change_only() {
+        let dir = workspace("on-change", &[("src/a.rs", "// TODO: x\n")]);
+        let list = ["todo", "list", "--output-on-change-only", "--out", "out.md"];
+        let unchanged = std::process::ExitCode::from(UNCHANGED_EXIT_CODE);
+        run_in(&dir, &["todo", "done", "--out", "out.md"]).unwrap();
+        std::fs::remove_file(dir.join("out.md")).unwrap();
+
+        assert_eq!(run_in(&dir, &list).unwrap(), unchanged);
+        assert!(!dir.join("out.md").exists());
+
+        std::fs::write(dir.join("src/a.rs"), "// TODO(0): x\n// TODO: y\n").unwrap();
+        assert_eq!(
+            run_in(&dir, &list).unwrap(),
+            std::process::ExitCode::SUCCESS
+        );
+        let out = std::fs::read_to_string(dir.join("out.md")).unwrap();
+        assert!(out.contains("y"), "{}", out);
+
+        // the new TODO is not tracked until `done`
+        assert_eq!(
+            run_in(&dir, &list).unwrap(),
+            std::process::ExitCode::SUCCESS
+        );
+        run_in(&dir, &["todo", "done", "--out", "out.md"]).unwrap();
+        assert_eq!(run_in(&dir, &list).unwrap(), unchanged);
+    }
+
+    #[test]
+    fn test_done_summary_only() {
+        let dir = workspace("summary-only", &[("src/a.rs", "// TODO: x\n// TODO: y\n")]);
+        std::fs::write(dir.join("out.md"), "kept").unwrap();
+
+        run_in(&dir, &["todo", "done", "--summary-only", "--out", "out.md"]).unwrap();
+        run_in(
+            &dir,
+            &[
+                "todo",
+                "done",
+                "--summary-only",
+                "--id",
+                "1",
+                "--out",
+                "out.md",
+            ],
+        )
+        .unwrap();
+
+        // the items are tracked, but the list is not written
+        let todo = read_json_data_file(&dir).unwrap();
+        assert_eq!(todo.items.len(), 2);
+        assert!(todo.items["1"].done);
+        assert_eq!(std::fs::read_to_string(dir.join("out.md")).unwrap(), "kept");
+
+        run_in(&dir, &["todo", "done", "--out", "out.md"]).unwrap();
+        assert_ne!(std::fs::read_to_string(dir.join("out.md")).unwrap(), "kept");
+    }
+
+    #[test]
+    fn test_confirm_injections() {
+        let dir = TempDir::new("confirm");
+        for (name, content) in [
+            ("a.rs", "// TODO: a\n"),
+            ("b.rs", "// TODO(7): tracked\n"),
+            ("c.rs", "// TODO: c\n"),
+        ] {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        let cfg = CliConfig {
+            include: vec![format!("{}/*.rs", dir.display())],
+            ..CliConfig::default()
+        };
+        let ids = |files: &[ScannedFile]| {
+            files
+                .iter()
+                .flat_map(|file| &file.matches)
+                .map(|m| m.id.clone().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // b.rs gets no new id and is not asked about
+        let (re, mut files) = scan_files(&[], None, &cfg, &Default::default()).unwrap();
+        assign_ids(&mut files, 0);
+        let mut answers = ["maybe\n", "y\n", "n\n"].into_iter();
+        let read_answer = || Ok(answers.next().map(str::to_string));
+        confirm_injections(&mut files, &re, &cfg.delimiters(), read_answer).unwrap();
+        assert_eq!(answers.next(), None);
+        assert_eq!(ids(&files), ["8", "7", "new-1"]);
+        assert!(files[0].matches[0].minted);
+        assert!(!files[2].matches[0].minted);
+
+        // no answer declines
+        let (re, mut files) = scan_files(&[], None, &cfg, &Default::default()).unwrap();
+        assign_ids(&mut files, 0);
+        confirm_injections(&mut files, &re, &cfg.delimiters(), || Ok(None)).unwrap();
+        assert_eq!(ids(&files), ["new-1", "7", "new-2"]);
+
+        // a dry run does not ask
+        let options = ScanOptions {
+            confirm_each: true,
+            dry_run: true,
+            ..Default::default()
+        };
+        let items = get_todos(&[], None, &cfg, &options).unwrap();
+        let mut ids = items.into_keys().collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, ["7", "8", "9"]);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a.rs")).unwrap(),
+            "// TODO: a\n"
+        );
+    }
+
+    #[test]
+    fn test_config_env() {
+        let dir = TempDir::new("config-env");
+        std::fs::write(dir.join(".git"), "").unwrap();
+        std::fs::write(
+            dir.join(CONFIG_PATH),
+            r#"{"patterns": ["FILE"], "include": []}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("other.json"),
+            r#"{"patterns": ["FLAG"], "include": []}"#,
+        )
+        .unwrap();
+        let env =
+            r#"{"patterns": ["ENV"], "include": [], "profiles": {"ci": {"max_title_width": 20}}}"#;
+        let patterns = |config: Option<&str>, env: Option<&str>| {
+            find_config(&dir, config.map(std::path::Path::new), env, None)
+                .unwrap()
+                .patterns
+        };
+
+        assert_eq!(patterns(Some("other.json"), Some(env)), ["FLAG"]);
+        assert_eq!(patterns(None, Some(env)), ["ENV"]);
+        assert_eq!(patterns(None, Some(" \n")), ["FILE"]);
+        assert_eq!(patterns(None, None), ["FILE"]);
+
+        let ci = find_config(&dir, None, Some(env), Some("ci")).unwrap();
+        assert_eq!(ci.max_title_width, Some(20));
+        let err = find_config(&dir, None, Some("{"), None).unwrap_err();
+        assert!(err.to_string().contains(CONFIG_ENV), "{}", err);
+    }
+}